@@ -0,0 +1,199 @@
+use chrono::Utc;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::error::FetchError;
+use crate::write_atomic;
+use shared_utils::paths::data_path;
+
+fn archive_dir() -> PathBuf {
+    data_path("archive")
+}
+
+const INDEX_FILE: &str = "index.json";
+const MATCH_FILE: &str = "match.json.gz";
+const TELEMETRY_FILE: &str = "telemetry.json.gz";
+
+// index.json luetaan ja kirjoitetaan kokonaan, ettei kaksi tallennusta hukkaa toistensa rivejä
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// One gzipped file in the archive, `path` is relative to the archive folder.
+/// `sha256` and `bytes` describe the uncompressed content, so they can be
+/// checked after decompressing.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArchivedFile {
+    pub path: String,
+    pub sha256: String,
+    pub bytes: u64,
+    pub compressed_bytes: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ArchiveEntry {
+    pub shard: String,
+    pub created_at: String,
+    pub archived_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_response: Option<ArchivedFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telemetry: Option<ArchivedFile>,
+}
+
+/// Raw match responses and telemetry, kept after PUBG deletes them.
+///
+/// Files live in `data/archive/{match_id}/` and are listed in
+/// `data/archive/index.json` by match id.
+#[derive(Debug, Default)]
+pub struct ArchiveIndex {
+    pub matches: BTreeMap<String, ArchiveEntry>,
+}
+
+impl ArchiveIndex {
+    pub fn load() -> Result<Self, FetchError> {
+        let mut index = ArchiveIndex::default();
+        if let Ok(mut file) = File::open(archive_dir().join(INDEX_FILE)) {
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).map_err(|e| FetchError::storage(format!("reading archive {}", INDEX_FILE), e))?;
+            index.matches = serde_json::from_str(&contents).map_err(|e| FetchError::json(INDEX_FILE, e))?;
+        }
+        Ok(index)
+    }
+
+    fn save(&self) -> Result<(), FetchError> {
+        let json_data = serde_json::to_string_pretty(&self.matches).map_err(|e| FetchError::json(INDEX_FILE, e))?;
+        write_atomic(&archive_dir().join(INDEX_FILE), json_data.as_bytes())
+    }
+}
+
+/// Feeds everything written through it to a SHA-256 hasher.
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Gzips `reader` into `{archive}/{match_id}/{name}`.
+fn compress_into_archive(match_id: &str, name: &str, mut reader: impl Read) -> Result<ArchivedFile, FetchError> {
+    let folder = archive_dir().join(match_id);
+    fs::create_dir_all(&folder).map_err(|e| FetchError::storage(format!("creating {}", folder.display()), e))?;
+    let path = folder.join(name);
+    let tmp_path = folder.join(format!("{}.tmp", name));
+
+    let file = File::create(&tmp_path).map_err(|e| FetchError::storage(format!("creating {}", tmp_path.display()), e))?;
+    let mut writer = HashingWriter { inner: GzEncoder::new(file, Compression::default()), hasher: Sha256::new(), bytes: 0 };
+    let written = io::copy(&mut reader, &mut writer).and_then(|_| writer.inner.try_finish());
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(FetchError::storage(format!("writing {}", tmp_path.display()), e));
+    }
+    fs::rename(&tmp_path, &path).map_err(|e| FetchError::storage(format!("replacing {}", path.display()), e))?;
+
+    let compressed_bytes = fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+    Ok(ArchivedFile {
+        path: format!("{}/{}", match_id, name),
+        sha256: format!("{:x}", writer.hasher.finalize()),
+        bytes: writer.bytes,
+        compressed_bytes,
+    })
+}
+
+fn update_index(match_id: &str, update: impl FnOnce(&mut ArchiveEntry)) -> Result<(), FetchError> {
+    let _guard = INDEX_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut index = ArchiveIndex::load()?;
+    let entry = index.matches.entry(match_id.to_string()).or_default();
+    update(entry);
+    entry.archived_at = Utc::now().to_rfc3339();
+    index.save()
+}
+
+/// Archives the `/matches/{id}` response. Already archived matches are left alone.
+pub fn store_match_response(match_id: &str, shard: &str, created_at: &str, response: &str) -> Result<(), FetchError> {
+    if ArchiveIndex::load()?.matches.get(match_id).is_some_and(|entry| entry.match_response.is_some()) {
+        return Ok(());
+    }
+
+    let archived = compress_into_archive(match_id, MATCH_FILE, response.as_bytes())?;
+    update_index(match_id, |entry| {
+        entry.shard = shard.to_string();
+        entry.created_at = created_at.to_string();
+        entry.match_response = Some(archived);
+    })
+}
+
+/// Archives the uncompressed telemetry read from `reader`. Already archived
+/// telemetry is left alone.
+pub fn store_telemetry(match_id: &str, reader: impl Read) -> Result<(), FetchError> {
+    if ArchiveIndex::load()?.matches.get(match_id).is_some_and(|entry| entry.telemetry.is_some()) {
+        return Ok(());
+    }
+
+    let archived = compress_into_archive(match_id, TELEMETRY_FILE, reader)?;
+    println!("Archived telemetry of {} ({} -> {} bytes)", match_id, archived.bytes, archived.compressed_bytes);
+    update_index(match_id, |entry| entry.telemetry = Some(archived))
+}
+
+fn open_archived(file: &ArchivedFile) -> Result<GzDecoder<BufReader<File>>, FetchError> {
+    let path = archive_dir().join(&file.path);
+    let opened = File::open(&path).map_err(|e| FetchError::storage(format!("reading {}", path.display()), e))?;
+    Ok(GzDecoder::new(BufReader::new(opened)))
+}
+
+/// Decompresses the file once and checks it against the index.
+fn verify(file: &ArchivedFile) -> Result<(), FetchError> {
+    let mut hashing = HashingWriter { inner: io::sink(), hasher: Sha256::new(), bytes: 0 };
+    io::copy(&mut open_archived(file)?, &mut hashing).map_err(|e| FetchError::storage(format!("decompressing {}", file.path), e))?;
+
+    let actual = format!("{:x}", hashing.hasher.finalize());
+    if actual != file.sha256 || hashing.bytes != file.bytes {
+        return Err(FetchError::Checksum { path: file.path.clone(), expected: file.sha256.clone(), actual });
+    }
+    Ok(())
+}
+
+fn archived_file(match_id: &str, pick: impl FnOnce(ArchiveEntry) -> Option<ArchivedFile>, what: &str) -> Result<ArchivedFile, FetchError> {
+    ArchiveIndex::load()?
+        .matches
+        .remove(match_id)
+        .and_then(pick)
+        .ok_or_else(|| FetchError::storage(format!("archive of match {}", match_id), format!("no archived {}", what)))
+}
+
+/// The archived `/matches/{id}` response, checksum verified.
+pub fn read_match_response(match_id: &str) -> Result<String, FetchError> {
+    let file = archived_file(match_id, |entry| entry.match_response, "match response")?;
+    verify(&file)?;
+
+    let mut contents = String::new();
+    open_archived(&file)?
+        .read_to_string(&mut contents)
+        .map_err(|e| FetchError::storage(format!("decompressing {}", file.path), e))?;
+    Ok(contents)
+}
+
+/// The archived telemetry for streaming, checksum verified before it is handed out.
+pub fn open_telemetry(match_id: &str) -> Result<Box<dyn Read>, FetchError> {
+    let file = archived_file(match_id, |entry| entry.telemetry, "telemetry")?;
+    verify(&file)?;
+    Ok(Box::new(BufReader::new(open_archived(&file)?)))
+}
@@ -0,0 +1,224 @@
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::archive::ArchiveIndex;
+use crate::error::FetchError;
+use crate::ingest_status::{stored_match_ids, IngestStatus, Status};
+use crate::pubg_client::PubgClient;
+use crate::roster;
+use crate::scheduler::QuietHours;
+use crate::seasons;
+use crate::telemetry;
+use shared_utils::paths::{data_dir, data_path};
+
+#[derive(Parser, Debug)]
+#[command(name = "api-fetch", about = "Fetches the matches of the tracked PUBG players and enriches their telemetry")]
+pub struct Cli {
+    /// Folder for matches, the archive and the ingest status
+    #[arg(long, global = true, env = "DATA_DIR", default_value = "data")]
+    pub data_dir: String,
+
+    #[arg(long, global = true, env = "REDIS_URL", default_value = "redis://127.0.0.1/")]
+    pub redis_url: String,
+
+    /// Only use the tracked players on this shard. Also the shard `fetch` looks the match up on.
+    #[arg(long, global = true)]
+    pub shard: Option<String>,
+
+    /// PUBG API to fetch from, e.g. the mock server of the workspace
+    #[arg(long, global = true, env = "PUBG_API_BASE_URL", default_value = crate::pubg_client::DEFAULT_BASE_URL)]
+    pub api_base_url: String,
+
+    /// Meters from a landing within which another team makes the drop hot
+    #[arg(long, global = true, env = "HOT_DROP_RADIUS", default_value_t = crate::landing::DEFAULT_HOT_DROP_RADIUS)]
+    pub hot_drop_radius: f32,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Args, Debug, Clone, Copy)]
+pub struct IngestArgs {
+    /// Telemetry files downloaded and enriched at the same time
+    #[arg(long, env = "INGEST_CONCURRENCY", default_value_t = crate::DEFAULT_INGEST_CONCURRENCY)]
+    pub concurrency: usize,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Check for new matches forever
+    Poll {
+        /// Seconds between polls, while someone is playing if an idle interval is set
+        #[arg(long, env = "POLL_INTERVAL", default_value_t = 30)]
+        interval: u64,
+        /// Seconds between polls when no tracked player has a match from the last hour
+        #[arg(long, env = "POLL_IDLE_INTERVAL")]
+        idle_interval: Option<u64>,
+        /// Each wait is randomly up to this many seconds shorter or longer
+        #[arg(long, env = "POLL_JITTER", default_value_t = 0)]
+        jitter: u64,
+        /// Comma separated local time windows without polling, e.g. `02:00-08:00,13:00-14:00`
+        #[arg(long, env = "POLL_QUIET_HOURS", value_delimiter = ',')]
+        quiet_hours: Vec<QuietHours>,
+        /// Seconds between season, lifetime and mastery refreshes, 0 turns them off
+        #[arg(long, env = "SEASON_INTERVAL", default_value_t = 6 * 60 * 60)]
+        season_interval: u64,
+        #[command(flatten)]
+        ingest: IngestArgs,
+    },
+    /// Fetch and store a single match
+    Fetch { match_id: String },
+    /// Ingest the missing matches among the newest 30 the API lists for the tracked players, then exit
+    Backfill {
+        #[command(flatten)]
+        ingest: IngestArgs,
+    },
+    /// Fetch season stats of the tracked players
+    Season {
+        /// Past season to fetch, the full id or its end such as `pc-2018-34`. Defaults to the current season.
+        #[arg(long = "season")]
+        season_id: Option<String>,
+        /// Only list the seasons of each shard
+        #[arg(long)]
+        list: bool,
+        /// Fetch the season list again instead of using `data/seasons.json`
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Fetch lifetime stats and weapon and survival mastery of the tracked players
+    Lifetime,
+    /// Rebuild stored matches from the raw archive
+    #[command(group(ArgGroup::new("selection").required(true).args(["match_id", "all"])))]
+    Reprocess {
+        #[arg(long = "match", value_name = "ID")]
+        match_id: Option<String>,
+        #[arg(long)]
+        all: bool,
+    },
+    /// Ingest previously downloaded match and telemetry files from a folder
+    Offline { dir: PathBuf },
+    /// Write the stored summaries and match events as one JSON document
+    Export {
+        /// File to write to instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Show what has been ingested and what is failing
+    Status,
+}
+
+/// `matches.json` and every `matches/{id}.json` in one document.
+pub fn export(output: Option<PathBuf>) -> Result<(), FetchError> {
+    let summaries: Value = match fs::read_to_string(data_path("matches.json")) {
+        Ok(contents) => serde_json::from_str(&contents).map_err(|e| FetchError::json("matches.json", e))?,
+        Err(_) => json!([]),
+    };
+
+    let mut events: BTreeMap<String, Value> = BTreeMap::new();
+    for match_id in stored_match_ids() {
+        let path = data_path(&format!("matches/{}.json", match_id));
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let parsed = serde_json::from_str(&contents).map_err(|e| FetchError::json(path.display().to_string(), e))?;
+                events.insert(match_id, parsed);
+            }
+            Err(e) => eprintln!("Skipping events of {}: {}", match_id, e),
+        }
+    }
+
+    let exported = serde_json::to_string_pretty(&json!({ "matches": summaries, "events": events }))
+        .map_err(|e| FetchError::json("export", e))?;
+    match output {
+        Some(path) => {
+            fs::write(&path, exported).map_err(|e| FetchError::storage(format!("writing {}", path.display()), e))?;
+            println!("Exported {} matches to {}", events.len(), path.display());
+        }
+        None => {
+            let mut stdout = std::io::stdout();
+            writeln!(stdout, "{}", exported).map_err(|e| FetchError::storage("writing stdout", e))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn status() -> Result<(), FetchError> {
+    let ingest_status = IngestStatus::load().map_err(|e| FetchError::storage("reading ingest status", e.to_string()))?;
+    let archive = ArchiveIndex::load()?;
+    let stored = stored_match_ids();
+
+    println!("Data folder: {}", data_dir().display());
+    println!("Tracked players:");
+    for player in roster::players() {
+        println!("  {}/{}", player.shard, player.name);
+    }
+    println!("Stored matches: {}", stored.len());
+    println!(
+        "Archived matches: {} ({} with telemetry)",
+        archive.matches.len(),
+        archive.matches.values().filter(|entry| entry.telemetry.is_some()).count()
+    );
+
+    let count = |status: Status| ingest_status.entries().values().filter(|entry| entry.status == status).count();
+    println!(
+        "Ingest status: {} done, {} failed, {} skipped",
+        count(Status::Done),
+        count(Status::Failed),
+        count(Status::Skipped)
+    );
+
+    let mut failed: Vec<_> = ingest_status.entries().iter().filter(|(_, entry)| entry.status == Status::Failed).collect();
+    failed.sort_by(|a, b| a.1.updated_at.cmp(&b.1.updated_at));
+    for (match_id, entry) in failed {
+        println!(
+            "  {} failed {} times, last at {}: {}",
+            match_id,
+            entry.attempts,
+            entry.updated_at,
+            entry.last_error.as_deref().unwrap_or("unknown error")
+        );
+    }
+
+    let mut unknown_events: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    let mut unknown_fields: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    let mut malformed_events: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for report in telemetry::load_unknown_events()? {
+        let unknown = report.unknown;
+        for (counts, seen) in [(unknown.events, &mut unknown_events), (unknown.fields, &mut unknown_fields), (unknown.malformed, &mut malformed_events)] {
+            for (name, count) in counts {
+                let (matches, events) = seen.entry(name).or_default();
+                *matches += 1;
+                *events += count;
+            }
+        }
+    }
+    for (title, seen) in [
+        ("Unknown telemetry events:", unknown_events),
+        ("Unknown telemetry fields:", unknown_fields),
+        ("Malformed telemetry events:", malformed_events),
+    ] {
+        if !seen.is_empty() {
+            println!("{}", title);
+            for (name, (matches, events)) in seen {
+                println!("  {}: {} events in {} matches", name, events, matches);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints the seasons of every tracked shard, newest first, the current one marked with `*`.
+pub async fn list_seasons(pubg_client: &PubgClient, refresh: bool) -> Result<(), FetchError> {
+    for shard in roster::shards() {
+        println!("{}:", shard);
+        for season in seasons::list(pubg_client, &shard, refresh).await?.iter().rev() {
+            let marker = if season.is_current_season { "*" } else { " " };
+            let offseason = if season.is_offseason { " (offseason)" } else { "" };
+            println!("  {} {}{}", marker, season.id, offseason);
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,3 @@
-pub mod player_names;
 pub mod map_names;
 pub mod weapons;
 pub mod attachments;
@@ -1,6 +0,0 @@
-pub const PLAYER_NAMES: [&str; 4] = [
-    "E1_Duderino",
-    "keken_viikset",
-    "HlGHLANDER",
-    "bold_moves_bob",
-];
@@ -0,0 +1,88 @@
+use std::fmt;
+
+/// Everything that can go wrong while ingesting a single match. A failed match
+/// is logged and retried on a later poll, it never takes the poll loop down.
+#[derive(Debug)]
+pub enum FetchError {
+    /// Connection problems and other transport level errors.
+    Http(reqwest::Error),
+    /// The API answered with a non-success status.
+    Status { url: String, status: u16 },
+    /// Still getting 429 after backing off `retries` times.
+    RateLimited { url: String, retries: u32 },
+    /// A response or file did not match the schema we expect.
+    Json { context: String, source: serde_json::Error },
+    /// The match has no telemetry asset, or the telemetry file could not be found.
+    MissingTelemetry { match_id: String, detail: String },
+    /// An `_D` or `createdAt` value that is not RFC 3339.
+    Timestamp { value: String, source: chrono::ParseError },
+    /// An archived file does not match the checksum in the archive index.
+    Checksum { path: String, expected: String, actual: String },
+    /// The selected season, or a current season when none is selected, is not in the `/seasons` list.
+    UnknownSeason { shard: String, season: Option<String> },
+    /// Reading or writing `data/` failed.
+    Storage { context: String, source: Box<dyn std::error::Error + Send + Sync> },
+    /// A blocking or spawned task panicked or was cancelled.
+    Task { context: String, source: tokio::task::JoinError },
+}
+
+impl FetchError {
+    pub fn json(context: impl Into<String>, source: serde_json::Error) -> Self {
+        FetchError::Json { context: context.into(), source }
+    }
+
+    pub fn storage(context: impl Into<String>, source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        FetchError::Storage { context: context.into(), source: source.into() }
+    }
+
+    pub fn task(context: impl Into<String>, source: tokio::task::JoinError) -> Self {
+        FetchError::Task { context: context.into(), source }
+    }
+
+    pub fn timestamp(value: &str, source: chrono::ParseError) -> Self {
+        FetchError::Timestamp { value: value.to_string(), source }
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Http(e) => write!(f, "HTTP error: {}", e),
+            FetchError::Status { url, status } => write!(f, "HTTP {} from {}", status, url),
+            FetchError::RateLimited { url, retries } => write!(f, "rate limited on {} after {} retries", url, retries),
+            FetchError::Json { context, source } => write!(f, "unexpected JSON in {}: {}", context, source),
+            FetchError::MissingTelemetry { match_id, detail } => write!(f, "no telemetry for match {}: {}", match_id, detail),
+            FetchError::Timestamp { value, source } => write!(f, "invalid timestamp {:?}: {}", value, source),
+            FetchError::Checksum { path, expected, actual } => write!(f, "checksum mismatch in {}: expected {}, got {}", path, expected, actual),
+            FetchError::UnknownSeason { shard, season: Some(season) } => write!(f, "no season {:?} on {}", season, shard),
+            FetchError::UnknownSeason { shard, season: None } => write!(f, "no current season on {}", shard),
+            FetchError::Storage { context, source } => write!(f, "storage error ({}): {}", context, source),
+            FetchError::Task { context, source } => write!(f, "task failed ({}): {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FetchError::Http(e) => Some(e),
+            FetchError::Json { source, .. } => Some(source),
+            FetchError::Timestamp { source, .. } => Some(source),
+            FetchError::Storage { source, .. } => Some(source.as_ref()),
+            FetchError::Task { source, .. } => Some(source),
+            FetchError::Status { .. } | FetchError::RateLimited { .. } | FetchError::MissingTelemetry { .. } | FetchError::Checksum { .. } | FetchError::UnknownSeason { .. } => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) => FetchError::Status {
+                url: e.url().map(|url| url.to_string()).unwrap_or_default(),
+                status: status.as_u16(),
+            },
+            None => FetchError::Http(e),
+        }
+    }
+}
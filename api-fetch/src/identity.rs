@@ -0,0 +1,254 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+
+use crate::error::FetchError;
+use crate::roster;
+use crate::write_atomic;
+use shared_utils::paths::data_path;
+use shared_utils::redis_loader::{forget_player_in_redis, load_player_aliases_to_redis};
+
+const REGISTRY_FILE: &str = "identities.json";
+// kansiot joissa on pelaajakohtainen {nimi}.json
+const PLAYER_FOLDERS: [&str; 7] = [
+    "season_stats",
+    "season_history",
+    "ranked_stats",
+    "ranked_history",
+    "lifetime_stats",
+    "weapon_mastery",
+    "survival_mastery",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NameRecord {
+    pub name: String,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+/// One PUBG account. `name` is the name the API returned last, `names` every
+/// name the account has had, oldest first.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Identity {
+    pub shard: String,
+    pub name: String,
+    pub names: Vec<NameRecord>,
+}
+
+/// Accounts seen in `/players` lookups, by account id, in `data/identities.json`.
+#[derive(Debug, Default)]
+pub struct Registry {
+    pub players: BTreeMap<String, Identity>,
+}
+
+impl Registry {
+    pub fn load() -> Result<Self, FetchError> {
+        let mut registry = Registry::default();
+        if let Ok(contents) = fs::read_to_string(data_path(REGISTRY_FILE)) {
+            registry.players = serde_json::from_str(&contents).map_err(|e| FetchError::json(REGISTRY_FILE, e))?;
+        }
+        Ok(registry)
+    }
+
+    fn save(&self) -> Result<(), FetchError> {
+        let json_data = serde_json::to_string_pretty(&self.players).map_err(|e| FetchError::json(REGISTRY_FILE, e))?;
+        write_atomic(&data_path(REGISTRY_FILE), json_data.as_bytes())
+    }
+
+    /// Account that has, or once had, `name` on `shard`.
+    pub fn account_id_of(&self, shard: &str, name: &str) -> Option<&str> {
+        // nykyinen nimi ensin, vanhan nimen voi ottaa joku toinen käyttöön
+        let current = self
+            .players
+            .iter()
+            .find(|(_, identity)| identity.shard == shard && identity.name.eq_ignore_ascii_case(name));
+        let former = || {
+            self.players
+                .iter()
+                .find(|(_, identity)| identity.shard == shard && identity.names.iter().any(|record| record.name.eq_ignore_ascii_case(name)))
+        };
+        current.or_else(former).map(|(account_id, _)| account_id.as_str())
+    }
+
+    pub fn current_name(&self, account_id: &str) -> Option<&str> {
+        self.players.get(account_id).map(|identity| identity.name.as_str())
+    }
+
+    /// What the lookup changed. `last_seen` is kept to the day so seeing the
+    /// same name again on every poll doesn't rewrite the registry.
+    fn observe(&mut self, shard: &str, account_id: &str, name: &str) -> Change {
+        let now = Utc::now().to_rfc3339();
+        let mut change = Change::Unchanged;
+        let identity = self.players.entry(account_id.to_string()).or_insert_with(|| {
+            change = Change::Updated;
+            Identity { shard: shard.to_string(), name: name.to_string(), names: Vec::new() }
+        });
+
+        if identity.name != name {
+            change = Change::Renamed(std::mem::replace(&mut identity.name, name.to_string()));
+        }
+        match identity.names.iter_mut().find(|record| record.name == name) {
+            Some(record) => {
+                if record.last_seen.get(..10) != now.get(..10) {
+                    record.last_seen = now;
+                    if change == Change::Unchanged {
+                        change = Change::Updated;
+                    }
+                }
+            }
+            None => {
+                identity.names.push(NameRecord { name: name.to_string(), first_seen: now.clone(), last_seen: now });
+                if change == Change::Unchanged {
+                    change = Change::Updated;
+                }
+            }
+        }
+        change
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Change {
+    Unchanged,
+    Updated,
+    /// The previous name of the account.
+    Renamed(String),
+}
+
+/// Records the `(account id, name)` pairs of a `/players` response. Returns
+/// whether the registry changed, it is only saved then.
+pub fn observe(shard: &str, players: &[(String, String)]) -> Result<bool, FetchError> {
+    let mut registry = Registry::load()?;
+    let mut changed = false;
+    let mut renames: Vec<(String, &str)> = Vec::new();
+    for (account_id, name) in players {
+        match registry.observe(shard, account_id, name) {
+            Change::Unchanged => {}
+            Change::Updated => changed = true,
+            Change::Renamed(previous) => {
+                println!("{} renamed to {} ({})", previous, name, account_id);
+                renames.push((previous, name));
+                changed = true;
+            }
+        }
+    }
+    if !changed {
+        return Ok(false);
+    }
+    registry.save()?;
+
+    for (previous, name) in renames {
+        move_player_files(&previous, name);
+    }
+
+    // vanhat nimet ohjataan nykyiseen myös http-palvelimella
+    if let Err(e) = load_player_aliases_to_redis() {
+        eprintln!("Failed to load player aliases into Redis: {}", e);
+    }
+    Ok(true)
+}
+
+/// Moves the stats and histories stored under the old name to the new one,
+/// so they keep growing instead of starting over.
+fn move_player_files(previous: &str, name: &str) {
+    for folder in PLAYER_FOLDERS {
+        let from = data_path(&format!("{}/{}.json", folder, previous));
+        let to = data_path(&format!("{}/{}.json", folder, name));
+        if !from.exists() {
+            continue;
+        }
+        if to.exists() {
+            eprintln!("Both {} and {} exist, keeping both", from.display(), to.display());
+            continue;
+        }
+        if let Err(e) = fs::rename(&from, &to) {
+            eprintln!("Failed to move {} to {}: {}", from.display(), to.display(), e);
+        }
+    }
+
+    if let Err(e) = forget_player_in_redis(previous) {
+        eprintln!("Failed to remove {} from Redis: {}", previous, e);
+    }
+}
+
+/// Account id of a tracked player, from the roster or from an earlier lookup.
+pub fn account_id(registry: &Registry, player: &roster::TrackedPlayer) -> Option<String> {
+    player
+        .account_id
+        .clone()
+        .or_else(|| registry.account_id_of(&player.shard, &player.name).map(str::to_string))
+}
+
+/// Who counts as a tracked player in telemetry and match summaries. Players
+/// whose account id is not known yet, e.g. on the first offline run, are
+/// matched by name until a lookup finds their id.
+#[derive(Debug, Clone, Default)]
+pub struct TrackedPlayers {
+    account_ids: HashSet<String>,
+    names: HashSet<String>,
+    current_names: HashMap<String, String>,
+}
+
+impl TrackedPlayers {
+    pub fn load() -> Self {
+        let registry = Registry::load().unwrap_or_else(|e| {
+            eprintln!("Failed to read the player registry, matching tracked players by name: {}", e);
+            Registry::default()
+        });
+
+        let mut tracked = TrackedPlayers::default();
+        for player in roster::players() {
+            match account_id(&registry, &player) {
+                Some(account_id) => {
+                    let name = registry.current_name(&account_id).unwrap_or(&player.name).to_string();
+                    tracked.current_names.insert(account_id.clone(), name);
+                    tracked.account_ids.insert(account_id);
+                }
+                None => {
+                    tracked.names.insert(player.name);
+                }
+            }
+        }
+        tracked
+    }
+
+    pub fn contains(&self, account_id: &str, name: &str) -> bool {
+        self.account_ids.contains(account_id) || self.names.contains(name)
+    }
+
+    /// Current name of a tracked account, `None` for everyone else.
+    pub fn current_name(&self, account_id: &str) -> Option<&str> {
+        self.current_names.get(account_id).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+impl TrackedPlayers {
+    /// Tracks exactly these accounts, for tests on hand built telemetry.
+    pub fn of(account_ids: &[&str]) -> Self {
+        TrackedPlayers { account_ids: account_ids.iter().map(|account_id| account_id.to_string()).collect(), ..TrackedPlayers::default() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_new_names_and_days_change_the_registry() {
+        let mut registry = Registry::default();
+        assert_eq!(registry.observe("steam", "account.1", "Alice"), Change::Updated);
+        assert_eq!(registry.observe("steam", "account.1", "Alice"), Change::Unchanged);
+
+        assert_eq!(registry.observe("steam", "account.1", "Alicia"), Change::Renamed("Alice".to_string()));
+        assert_eq!(registry.observe("steam", "account.1", "Alicia"), Change::Unchanged);
+        assert_eq!(registry.players["account.1"].names.len(), 2);
+
+        // eilen nähty nimi päivitetään kerran päivässä
+        registry.players.get_mut("account.1").unwrap().names[1].last_seen = "2025-05-01T18:00:00+00:00".to_string();
+        assert_eq!(registry.observe("steam", "account.1", "Alicia"), Change::Updated);
+        assert_eq!(registry.observe("steam", "account.1", "Alicia"), Change::Unchanged);
+    }
+}
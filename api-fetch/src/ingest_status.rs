@@ -0,0 +1,129 @@
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use shared_utils::paths::data_path;
+
+use crate::write_atomic;
+
+const STATUS_FILE: &str = "ingest_status.json";
+// PUBG säilyttää matsit 14 päivää, kuukauden jälkeen statusta ei enää tarvita
+const KEEP_DAYS: i64 = 30;
+pub const MAX_ATTEMPTS: u32 = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Done,
+    Failed,
+    /// Older than the newest `MAX_STORED_MATCHES`, would be trimmed right away.
+    Skipped,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MatchStatus {
+    pub status: Status,
+    /// Failed attempts, a later success keeps the count.
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub updated_at: String,
+}
+
+/// Per-match ingestion state, persisted to `data/ingest_status.json` so a
+/// match that failed halfway is picked up again on the next poll.
+#[derive(Debug, Default)]
+pub struct IngestStatus {
+    matches: HashMap<String, MatchStatus>,
+}
+
+impl IngestStatus {
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let mut status = IngestStatus::default();
+
+        if let Ok(mut file) = File::open(data_path(STATUS_FILE)) {
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            status.matches = serde_json::from_str(&contents)?;
+        }
+
+        // matsi jolla on sekä rivi matches.json:ssa että telemetria tiedosto on valmis,
+        // vaikka status tiedosto puuttuisi
+        for id in stored_match_ids() {
+            if !status.matches.contains_key(&id) && data_path(&format!("matches/{}.json", id)).exists() {
+                status.set(&id, Status::Done, None);
+            }
+        }
+        Ok(status)
+    }
+
+    pub fn save(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let cutoff = (Utc::now() - Duration::days(KEEP_DAYS)).to_rfc3339();
+        self.matches.retain(|_, entry| entry.updated_at > cutoff);
+
+        let json_data = serde_json::to_string_pretty(&self.matches)?;
+        write_atomic(&data_path(STATUS_FILE), json_data.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &HashMap<String, MatchStatus> {
+        &self.matches
+    }
+
+    /// Whether the match still needs to be (re)ingested.
+    pub fn needs_ingest(&self, match_id: &str) -> bool {
+        match self.matches.get(match_id) {
+            None => true,
+            Some(entry) => entry.status == Status::Failed && entry.attempts < MAX_ATTEMPTS,
+        }
+    }
+
+    pub fn mark_done(&mut self, match_id: &str) {
+        self.set(match_id, Status::Done, None);
+    }
+
+    pub fn mark_skipped(&mut self, match_id: &str) {
+        self.set(match_id, Status::Skipped, None);
+    }
+
+    pub fn mark_failed(&mut self, match_id: &str, error: String) {
+        self.set(match_id, Status::Failed, Some(error));
+        if let Some(entry) = self.matches.get(match_id) {
+            if entry.attempts >= MAX_ATTEMPTS {
+                eprintln!("Giving up on match {} after {} attempts", match_id, entry.attempts);
+            }
+        }
+    }
+
+    fn set(&mut self, match_id: &str, status: Status, last_error: Option<String>) {
+        let entry = self.matches.entry(match_id.to_string()).or_insert(MatchStatus {
+            status,
+            attempts: 0,
+            last_error: None,
+            updated_at: String::new(),
+        });
+        entry.status = status;
+        if status == Status::Failed {
+            entry.attempts += 1;
+        }
+        entry.last_error = last_error;
+        entry.updated_at = Utc::now().to_rfc3339();
+    }
+}
+
+pub fn stored_match_ids() -> Vec<String> {
+    let mut contents = String::new();
+    if let Ok(mut file) = File::open(data_path("matches.json")) {
+        if file.read_to_string(&mut contents).is_ok() {
+            if let Ok(parsed) = serde_json::from_str::<Vec<HashMap<String, Value>>>(&contents) {
+                return parsed
+                    .iter()
+                    .filter_map(|single_match| single_match.get("id")?.as_str().map(String::from))
+                    .collect();
+            }
+        }
+    }
+    Vec::new()
+}
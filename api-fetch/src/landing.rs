@@ -0,0 +1,330 @@
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::OnceLock;
+
+use crate::error::FetchError;
+use crate::identity::TrackedPlayers;
+use crate::telemetry_events::TelemetryEvent;
+use crate::write_atomic;
+use crate::Location;
+use shared_utils::paths::data_path;
+use shared_utils::redis_loader::load_landings_to_redis;
+
+const LANDINGS_FILE: &str = "landings.json";
+pub const DEFAULT_HOT_DROP_RADIUS: f32 = 200.0;
+// kuinka kauan squadin pitää olla hengissä laskeutumisen jälkeen
+const SURVIVAL_SECONDS: i64 = 5 * 60;
+const AIRCRAFT: &str = "TransportAircraft";
+
+static HOT_DROP_RADIUS: OnceLock<f32> = OnceLock::new();
+
+/// Sets `--hot-drop-radius`, the first call wins.
+pub fn set_hot_drop_radius(radius: f32) {
+    let _ = HOT_DROP_RADIUS.set(radius);
+}
+
+/// Meters from a landing within which another team's landing makes the drop
+/// hot, 200 unless `--hot-drop-radius` says otherwise.
+fn hot_drop_radius() -> f32 {
+    HOT_DROP_RADIUS.get().copied().unwrap_or(DEFAULT_HOT_DROP_RADIUS)
+}
+
+struct Touchdown {
+    account_id: String,
+    name: String,
+    team_id: i32,
+    time: DateTime<FixedOffset>,
+    location: Location,
+    zone: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerLanding {
+    pub name: String,
+    pub account_id: String,
+    /// Seconds since `LogMatchDefinition`, when the player left the plane.
+    pub jump_time: Option<f32>,
+    pub landing_time: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    /// Named location the player landed in, if any.
+    pub zone: Option<String>,
+    pub teams_nearby: usize,
+    pub survived: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SquadLanding {
+    pub team_id: i32,
+    /// Zone of the first member on the ground.
+    pub zone: Option<String>,
+    /// Other teams that landed within the radius of any member.
+    pub teams_nearby: usize,
+    pub hot_drop: bool,
+    /// Someone in the squad was still alive five minutes after the first landing.
+    pub survived: bool,
+    pub players: Vec<PlayerLanding>,
+}
+
+/// Drops of the tracked squads in one match, kept for every match in
+/// `data/matches.json` in `data/landings.json`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MatchLanding {
+    pub match_id: String,
+    pub map_name: String,
+    pub game_mode: String,
+    pub date: String,
+    pub hot_drop_radius: f32,
+    pub squads: Vec<SquadLanding>,
+}
+
+/// Landings, jumps and deaths of every player, collected from the telemetry.
+#[derive(Default)]
+pub struct Landings {
+    touchdowns: Vec<Touchdown>,
+    jumps: HashMap<String, DateTime<FixedOffset>>,
+    deaths: HashMap<String, DateTime<FixedOffset>>,
+}
+
+impl Landings {
+    pub fn visit(&mut self, event: &TelemetryEvent) {
+        match event {
+            TelemetryEvent::LogParachuteLanding(e) => {
+                let (Some(character), Ok(time)) = (&e.character, DateTime::parse_from_rfc3339(&e.date)) else {
+                    return;
+                };
+                // laskuvarjolla voi laskeutua myöhemminkin, vain ensimmäinen on pudotus
+                if self.touchdowns.iter().any(|touchdown| touchdown.account_id == character.account_id) {
+                    return;
+                }
+                self.touchdowns.push(Touchdown {
+                    account_id: character.account_id.clone(),
+                    name: character.name.clone(),
+                    team_id: character.team_id,
+                    time,
+                    location: character.location.clone(),
+                    zone: character.zone.first().cloned(),
+                });
+            }
+            TelemetryEvent::LogVehicleLeave(e) if e.vehicle.as_ref().is_some_and(|vehicle| vehicle.vehicle_type == AIRCRAFT) => {
+                if let (Some(character), Ok(time)) = (&e.character, DateTime::parse_from_rfc3339(&e.date)) {
+                    self.jumps.entry(character.account_id.clone()).or_insert(time);
+                }
+            }
+            TelemetryEvent::LogPlayerKillV2(e) => {
+                if let (Some(victim), Some(date)) = (&e.victim, &e.event_time) {
+                    if let Ok(time) = DateTime::parse_from_rfc3339(date) {
+                        self.deaths.entry(victim.account_id.clone()).or_insert(time);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Landing analysis of the squads with a tracked player, `None` when
+    /// none of them parachuted, e.g. in arcade modes.
+    pub fn analyze(self, match_id: &str, summary: &HashMap<String, serde_json::Value>, match_start_time: &str, squads: &HashMap<i32, Vec<String>>, tracked: &TrackedPlayers) -> Option<MatchLanding> {
+        let start = DateTime::parse_from_rfc3339(match_start_time).ok();
+        let seconds = |time: DateTime<FixedOffset>| start.map(|start| (time - start).num_milliseconds() as f32 / 1000.0).unwrap_or(0.0);
+        let radius = hot_drop_radius();
+        let survival = Duration::seconds(SURVIVAL_SECONDS);
+        let survived_until = |account_id: &str, time: DateTime<FixedOffset>| self.deaths.get(account_id).is_none_or(|death| *death > time + survival);
+
+        let tracked_teams: HashSet<i32> = self
+            .touchdowns
+            .iter()
+            .filter(|touchdown| tracked.contains(&touchdown.account_id, &touchdown.name))
+            .map(|touchdown| touchdown.team_id)
+            .collect();
+        let mut team_ids: Vec<i32> = tracked_teams.into_iter().collect();
+        team_ids.sort();
+
+        let mut squad_landings: Vec<SquadLanding> = Vec::new();
+        for team_id in team_ids {
+            let mut members: Vec<&Touchdown> = self.touchdowns.iter().filter(|touchdown| touchdown.team_id == team_id).collect();
+            members.sort_by_key(|touchdown| touchdown.time);
+            let Some(first) = members.first() else {
+                continue;
+            };
+
+            let teams_near = |touchdown: &Touchdown| -> HashSet<i32> {
+                self.touchdowns
+                    .iter()
+                    .filter(|other| other.team_id != team_id && other.location.distance(&touchdown.location) <= radius)
+                    .map(|other| other.team_id)
+                    .collect()
+            };
+
+            let mut squad_nearby: HashSet<i32> = HashSet::new();
+            let mut players: Vec<PlayerLanding> = Vec::new();
+            for touchdown in &members {
+                let nearby = teams_near(touchdown);
+                squad_nearby.extend(&nearby);
+                players.push(PlayerLanding {
+                    name: tracked.current_name(&touchdown.account_id).unwrap_or(&touchdown.name).to_string(),
+                    account_id: touchdown.account_id.clone(),
+                    jump_time: self.jumps.get(&touchdown.account_id).map(|time| seconds(*time)),
+                    landing_time: seconds(touchdown.time),
+                    x: touchdown.location.x,
+                    y: touchdown.location.y,
+                    z: touchdown.location.z,
+                    zone: touchdown.zone.clone(),
+                    teams_nearby: nearby.len(),
+                    survived: survived_until(&touchdown.account_id, touchdown.time),
+                });
+            }
+
+            // squad selviää jos joku sen jäsenistä, laskeutunut tai ei, on vielä hengissä
+            let squad_members = squads.get(&team_id).cloned().unwrap_or_else(|| members.iter().map(|touchdown| touchdown.account_id.clone()).collect());
+            let survived = squad_members.iter().any(|account_id| survived_until(account_id, first.time));
+
+            squad_landings.push(SquadLanding {
+                team_id,
+                zone: first.zone.clone(),
+                teams_nearby: squad_nearby.len(),
+                hot_drop: !squad_nearby.is_empty(),
+                survived,
+                players,
+            });
+        }
+
+        if squad_landings.is_empty() {
+            return None;
+        }
+        let text = |key: &str| summary.get(key).and_then(|value| value.as_str()).unwrap_or_default().to_string();
+        Some(MatchLanding {
+            match_id: match_id.to_string(),
+            map_name: text("map_name"),
+            game_mode: text("game_mode"),
+            date: text("date"),
+            hot_drop_radius: radius,
+            squads: squad_landings,
+        })
+    }
+}
+
+fn load() -> Result<Vec<MatchLanding>, FetchError> {
+    match fs::read_to_string(data_path(LANDINGS_FILE)) {
+        Ok(contents) => serde_json::from_str(&contents).map_err(|e| FetchError::json(LANDINGS_FILE, e)),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// Adds the match to `data/landings.json`, replacing an earlier analysis of
+/// the same match.
+pub fn record(landing: &MatchLanding) -> Result<(), FetchError> {
+    let mut landings = load()?;
+    landings.retain(|stored| stored.match_id != landing.match_id);
+    landings.push(landing.clone());
+    landings.sort_by(|a, b| a.date.cmp(&b.date));
+    save(&landings)
+}
+
+/// Drops the matches trimmed from `data/matches.json`.
+pub fn forget(match_ids: &[String]) -> Result<(), FetchError> {
+    let mut landings = load()?;
+    let count = landings.len();
+    landings.retain(|stored| !match_ids.contains(&stored.match_id));
+    if landings.len() == count {
+        return Ok(());
+    }
+    save(&landings)
+}
+
+fn save(landings: &[MatchLanding]) -> Result<(), FetchError> {
+    let json_data = serde_json::to_string_pretty(landings).map_err(|e| FetchError::json(LANDINGS_FILE, e))?;
+    write_atomic(&data_path(LANDINGS_FILE), json_data.as_bytes())?;
+
+    if let Err(e) = load_landings_to_redis() {
+        eprintln!("Failed to load landings into Redis: {}", e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry_events::parse;
+    use serde_json::{json, Value};
+
+    const START: &str = "2025-05-01T18:00:00Z";
+
+    fn at(seconds: i64) -> String {
+        (DateTime::parse_from_rfc3339(START).unwrap() + Duration::seconds(seconds)).to_rfc3339()
+    }
+
+    // tapoissa uhrin kaikki kentät ovat pakollisia
+    fn character(account_id: &str, team_id: i32, x: f32) -> Value {
+        json!({
+            "name": account_id, "accountId": account_id, "teamId": team_id, "health": 100.0, "ranking": 0, "individualRanking": 0,
+            "isInVehicle": false, "location": {"x": x, "y": 0.0, "z": 0.0}
+        })
+    }
+
+    fn event(kind: &str, seconds: i64, fields: Value) -> TelemetryEvent {
+        let mut value = json!({"_T": kind, "_D": at(seconds), "common": {"isGame": 1.0}});
+        value.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+        parse(value)
+    }
+
+    fn touchdown(seconds: i64, account_id: &str, team_id: i32, x: f32) -> TelemetryEvent {
+        event("LogParachuteLanding", seconds, json!({"character": character(account_id, team_id, x)}))
+    }
+
+    fn kill(seconds: i64, victim: &str) -> TelemetryEvent {
+        event("LogPlayerKillV2", seconds, json!({"victim": character(victim, 1, 0.0), "location": {"x": 0.0, "y": 0.0, "z": 0.0}}))
+    }
+
+    fn analyze(tracked: &[&str], events: Vec<TelemetryEvent>) -> MatchLanding {
+        let mut landings = Landings::default();
+        for event in &events {
+            landings.visit(event);
+        }
+        let summary = HashMap::from([("map_name".to_string(), json!("Erangel"))]);
+        landings.analyze("match", &summary, START, &HashMap::new(), &TrackedPlayers::of(tracked)).unwrap()
+    }
+
+    #[test]
+    fn counts_teams_landing_within_the_radius() {
+        let landing = analyze(
+            &["a"],
+            vec![
+                event("LogVehicleLeave", 60, json!({"character": character("a", 1, 0.0), "vehicle": {"vehicleType": AIRCRAFT}})),
+                touchdown(100, "a", 1, 0.0),
+                touchdown(105, "b", 1, 30000.0),
+                // c 150 m molemmista, d 150 m b:stä, e yli kilometrin päässä
+                touchdown(110, "c", 2, 15000.0),
+                touchdown(112, "d", 3, 45000.0),
+                touchdown(115, "e", 4, 130000.0),
+            ],
+        );
+        assert_eq!(landing.map_name, "Erangel");
+        assert_eq!(landing.squads.len(), 1);
+        let squad = &landing.squads[0];
+        assert_eq!(squad.teams_nearby, 2);
+        assert!(squad.hot_drop);
+        let nearby: Vec<usize> = squad.players.iter().map(|player| player.teams_nearby).collect();
+        assert_eq!(nearby, [1, 2]);
+        let jumps: Vec<Option<f32>> = squad.players.iter().map(|player| player.jump_time).collect();
+        assert_eq!(jumps, [Some(60.0), None]);
+        assert_eq!(squad.players[0].landing_time, 100.0);
+    }
+
+    #[test]
+    fn survives_when_alive_five_minutes_after_landing() {
+        let landing = analyze(&["a"], vec![touchdown(100, "a", 1, 0.0), touchdown(200, "b", 1, 0.0), kill(399, "a"), kill(501, "b")]);
+        let squad = &landing.squads[0];
+        assert!(!squad.hot_drop);
+        let survived: Vec<bool> = squad.players.iter().map(|player| player.survived).collect();
+        assert_eq!(survived, [false, true]);
+        // squadin viisi minuuttia lasketaan ensimmäisestä laskeutumisesta
+        assert!(squad.survived);
+
+        let landing = analyze(&["a"], vec![touchdown(100, "a", 1, 0.0), touchdown(200, "b", 1, 0.0), kill(399, "a"), kill(400, "b")]);
+        assert!(!landing.squads[0].survived);
+    }
+}
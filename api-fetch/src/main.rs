@@ -1,55 +1,72 @@
-use reqwest::{Client, header};
-use serde_json::{json, Value, from_str};
+use serde_json::{json, Map, Value, from_str};
 use serde::{Serialize, Deserialize, Deserializer};
 //use std::error::Error;
 use std::env;
 use dotenv::dotenv;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{BufReader, Read, Write};
 use std::collections::HashSet;
-use chrono::{DateTime};
+use chrono::{DateTime, FixedOffset, Local};
 use std::collections::HashMap;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
 use tokio::time::sleep;
+use futures::stream::{self, StreamExt};
+mod archive;
+mod cli;
 mod constants;
+mod error;
+mod identity;
+mod ingest_status;
+mod mastery;
+mod landing;
+mod movement;
+mod zones;
+mod offline;
+mod pubg_client;
+mod ranked;
+mod reprocess;
+mod roster;
+mod scheduler;
+mod season_data;
+mod season_history;
+mod seasons;
+mod telemetry;
+mod telemetry_events;
 use shared_utils::redis_loader::load_matches_summary_data_into_redis;
 use shared_utils::redis_loader::load_single_match_to_redis;
 use shared_utils::redis_loader::delete_single_match_from_redis;
-
-fn player_id_merge () -> String {
-    let mut players_ids_merged = String::new();
-
-    for (index, name) in constants::player_names::PLAYER_NAMES.iter().enumerate() {
-        if index == constants::player_names::PLAYER_NAMES.len() -1 {
-            players_ids_merged.push_str(name);
-        } else {
-            players_ids_merged.push_str(name);
-            players_ids_merged.push_str("%2C");
-        }
-    }
-    return players_ids_merged;
-}
-
-fn make_player_id_url () -> String {
-    let mut player_url = String::new();
-    player_url.push_str("https://api.pubg.com/shards/steam/players?filter[playerNames]=");
-    let player_ids = player_id_merge();
-    player_url.push_str(&player_ids);
-    return player_url;
-}
+use shared_utils::paths::data_path;
+use pubg_client::PubgClient;
+use ingest_status::IngestStatus;
+use offline::TelemetrySource;
+use telemetry::TelemetryCollector;
+use identity::TrackedPlayers;
+use scheduler::Scheduler;
+use error::FetchError;
+use cli::{Cli, Command};
+use clap::Parser;
+
+const DEFAULT_INGEST_CONCURRENCY: usize = 4;
+const MAX_STORED_MATCHES: usize = 30;
 
 #[derive(Debug, Deserialize)]
 struct PlayerIdData {
     data: Vec<PlayerId>,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct PlayerId {
     id: String,
+    attributes: PlayerIdAttributes,
     relationships: Relationships,
 }
 
+#[derive(Debug, Deserialize)]
+struct PlayerIdAttributes {
+    name: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct Relationships {
     matches: MatchData,
@@ -65,8 +82,13 @@ struct Match {
     id: String,
 }
 
-async fn extract_user_id(response: &str) -> Vec<String> {
-    let parsed: PlayerIdData = serde_json::from_str(response).expect("Failed to parse JSON");
+fn extract_user_id(response: &str, shard: &str) -> Result<Vec<String>, FetchError> {
+    let parsed: PlayerIdData = serde_json::from_str(response).map_err(|e| FetchError::json("player lookup", e))?;
+
+    let players: Vec<(String, String)> = parsed.data.iter().map(|player| (player.id.clone(), player.attributes.name.clone())).collect();
+    if let Err(e) = identity::observe(shard, &players) {
+        eprintln!("Failed to update the player registry: {}", e);
+    }
 
     let mut match_ids: Vec<String> = Vec::new();
 
@@ -79,33 +101,22 @@ async fn extract_user_id(response: &str) -> Vec<String> {
             }
         }
     }
-    return match_ids;
-}
-
-fn make_headers(api_key: &String) -> Result<header::HeaderMap, Box<dyn std::error::Error>> {
-    let mut headers = header::HeaderMap::new();
-    headers.insert(header::ACCEPT, "application/vnd.api+json".parse()?);
-    headers.insert(header::AUTHORIZATION, format!("Bearer {}", api_key).parse()?);
-    return Ok(headers);
+    Ok(match_ids)
 }
 
-#[allow(dead_code)]
+/// The whole `/matches/{id}` response, parsed once.
 #[derive(Debug, Deserialize)]
-struct MatchOverview {
+struct MatchResponse {
     data: MatchOverviewData,
+    included: Vec<MatchOverviewIncluded>,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct MatchOverviewData {
-    #[serde(rename = "type")]
-    match_type: String,
     id: String,
     attributes: MatchOverviewAttributes,
-    relationships: MatchOverviewRelationships,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct MatchOverviewAttributes {
     #[serde(rename = "createdAt")]
@@ -116,55 +127,36 @@ struct MatchOverviewAttributes {
 
     #[serde(rename = "mapName")]
     map_name: String,
-}
 
-#[derive(Debug, Deserialize)]
-struct MatchOverviewInclude {
-    included: Vec<MatchOverviewIncluded>,
+    // "official", "competitive" (ranked), "custom" ...
+    #[serde(rename = "matchType", default)]
+    match_type: Option<String>,
+
+    #[serde(rename = "shardId")]
+    shard_id: Option<String>,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
 enum MatchOverviewIncluded {
     #[serde(rename = "asset")]
     Asset {
-        id: String,
         attributes: AssetAttributes,
     },
     #[serde(rename = "participant")]
     Participant {
-        id: String,
         attributes: ParticipantAttributes,
     },
+    // squadit saadaan telemetriasta, rosterin sijoitus on jo osallistujilla
     #[serde(rename = "roster")]
-    Roster {
-        id: String,
-        attributes: RosterAttributes,
-    },
-}
-
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct RosterAttributes {
-    stats: RosterStats,
-}
-
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct RosterStats {
-    rank: i32,
-    #[serde(rename = "teamId")]
-    team_id: i32,
+    Roster,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct ParticipantAttributes {
     stats: ParticipantStats,
 }
 
-#[allow(dead_code)]
 #[derive(Serialize, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ParticipantStats {
@@ -193,37 +185,6 @@ struct AssetAttributes {
     url: String,
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct MatchOverviewRelationships {
-    rosters: MatchOverviewRosters,
-    assets: MatchOverviewAssets,
-}
-
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct MatchOverviewRosters {
-    data: Vec<MatchRosterData>,
-}
-
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct MatchRosterData {
-    id: String,
-}
-
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct MatchOverviewAssets {
-    data: Vec<MatchAssetData>,
-}
-
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct MatchAssetData {
-    id: String,
-}
-
 #[derive(Serialize, Debug, Deserialize, Clone)]
 struct Event {
     #[serde(rename = "attackId")]
@@ -300,6 +261,11 @@ struct Event {
     victim_helmet: Option<Armor>,
 
     victim_vest: Option<Armor>,
+
+    /// Fields of the telemetry event that are not modeled above, written back
+    /// as they came so nothing is lost when the event is stored.
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Debug, Deserialize, Clone)]
@@ -312,6 +278,8 @@ struct Armor {
 struct ItemEquipItem {
     #[serde(rename = "itemId")]
     item_id: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Debug, Deserialize, Clone)]
@@ -324,15 +292,9 @@ struct AddionalDamageInfo {
     weapon: String,
     #[serde(rename = "additionalInfo")]
     attachments: Vec<String>,
-    distance: f32
-}
-
-#[derive(Serialize, Debug, Deserialize, Clone)]
-struct BrokenArmor {
-    #[serde(rename = "itemId")]
-    item_id: String,
-    #[serde(rename = "subCategory")]
-    sub_category: String
+    distance: f32,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Debug, Deserialize, Clone)]
@@ -340,7 +302,9 @@ struct AttackWeapon {
     #[serde(rename = "itemId")]
     weapon: String,
     #[serde(rename = "attachedItems")]
-    attachments: Vec<String>
+    attachments: Vec<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Debug, Deserialize, Clone)]
@@ -356,7 +320,9 @@ struct Target {
     account_id: String,
     #[serde(rename = "isInVehicle")]
     is_in_vehicle: bool,
-    location: Location
+    location: Location,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -396,15 +362,7 @@ impl<'de> Deserialize<'de> for ActionType {
      }
 }
 
-#[derive(Serialize, Debug, Deserialize, Clone)]
-struct Player {
-    name: String,
-    #[serde(rename = "teamId")]
-    team_id: i32,
-    health: f64,
-}
-
-#[derive(Serialize, Debug, Deserialize, Clone)]
+#[derive(Serialize, Debug, Deserialize, Clone, Default)]
 struct Location {
     x: f32,
     y: f32,
@@ -434,7 +392,7 @@ fn format_data_names(data: &mut AddionalDamageInfo) {
         .unwrap_or_else(|| "Unknown".to_string());
 
     for attachment in &mut data.attachments {
-        if let Some(&(_, ref new_attachment_name)) = constants::attachments::ATTACHMENTS
+        if let Some((_, new_attachment_name)) = constants::attachments::ATTACHMENTS
             .iter()
             .find(|&&(key, _)| key == *attachment)
         {
@@ -443,33 +401,39 @@ fn format_data_names(data: &mut AddionalDamageInfo) {
     }
 }
 
-fn make_match_summary_with_full_squad(all_squads: &mut HashMap<i32, Vec<String>>, parsed_include_data_for_passing: MatchOverviewInclude, parsed_match_data: MatchOverview, id: &String, parsed_for_all_events: Vec<Event>)
--> Result<bool, Box<dyn std::error::Error>> {
+/// Renames tracked players in an event to their current name, so a renamed
+/// player's matches stay under one name.
+fn use_current_names(event: &mut Event, tracked: &TrackedPlayers) {
+    let targets = [&mut event.attacker, &mut event.victim, &mut event.dbno_maker, &mut event.finisher, &mut event.killer, &mut event.player];
+    for target in targets.into_iter().flatten() {
+        if let Some(current_name) = tracked.current_name(&target.account_id) {
+            target.name = current_name.to_string();
+        }
+    }
+}
+
+/// `all_squads` and `finisher_kills` hold account ids, squads with a
+/// tracked player end up in the summary.
+fn make_match_summary_with_full_squad(all_squads: &HashMap<i32, Vec<String>>, mut single_player_performance: Vec<ParticipantAttributes>, match_attributes: MatchOverviewAttributes, id: &String, shard: &str, finisher_kills: &[String], tracked: &TrackedPlayers)
+-> HashMap<String, Value> {
 
    let mut full_squad:Vec<String> = Vec::new();
 
+   // pelaajat joiden id:tä ei vielä tiedetä seurataan nimellä, nimi löytyy osallistujista
+   let participant_names: HashMap<&str, &str> = single_player_performance
+       .iter()
+       .map(|player| (player.stats.player_id.as_str(), player.stats.name.as_str()))
+       .collect();
+
    for squad in all_squads.values() {
-       for player_name in constants::player_names::PLAYER_NAMES.iter() {
-           if squad.contains(&player_name.to_string()) {
-               full_squad.extend(squad.clone());
-           }
+       if squad.iter().any(|account_id| tracked.contains(account_id, participant_names.get(account_id.as_str()).unwrap_or(&""))) {
+           full_squad.extend(squad.clone());
        }
    }
 
-    let mut single_player_performance: Vec<ParticipantAttributes> = Vec::new();
-
-    for item in parsed_include_data_for_passing.included {
-            match item {
-                MatchOverviewIncluded::Participant {attributes, ..} => {
-                    single_player_performance.push(attributes)
-                }
-                _ => {}
-            }
-        }
-
-    let date = parsed_match_data.data.attributes.created_at;
-    let game_mode = parsed_match_data.data.attributes.game_mode;
-    let map_name = parsed_match_data.data.attributes.map_name;
+    let date = match_attributes.created_at;
+    let game_mode = match_attributes.game_mode;
+    let map_name = match_attributes.map_name;
     let actual_map_name = constants::map_names::MAP_NAME
         .iter()
         .find(|&&(key, _)| key == map_name)
@@ -479,37 +443,20 @@ fn make_match_summary_with_full_squad(all_squads: &mut HashMap<i32, Vec<String>>
     let mut squad: Vec<&mut ParticipantStats> = single_player_performance
         .iter_mut()
         .map(|player| &mut player.stats)
-        .filter(|player| full_squad.contains(&player.name))
+        .filter(|player| full_squad.contains(&player.player_id))
         .collect();
 
-    for player_stats in &mut squad {
-        for event in &parsed_for_all_events {
-            match event.action {
-                Some(ActionType::LogPlayerKillV2) => {
-                    if let Some(kill_event) = &event.finisher {
-                        if kill_event.name == player_stats.name {
-                            player_stats.kills = player_stats.kills + 1;
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
-    }
-
     for player_stats in &mut squad {
         player_stats.kills = 0;
+        if let Some(current_name) = tracked.current_name(&player_stats.player_id) {
+            player_stats.name = current_name.to_string();
+        }
     }
 
-    for event in &parsed_for_all_events {
-        if let Some(ActionType::LogPlayerKillV2) = event.action {
-            if let (Some(kill_event), Some(id)) = (&event.finisher, &event.attack_id)  {
-                if *id != -1 { continue };
-                for player_stats in &mut squad {
-                    if kill_event.name == player_stats.name {
-                        player_stats.kills += 1;
-                    }
-                }
+    for finisher in finisher_kills {
+        for player_stats in &mut squad {
+            if *finisher == player_stats.player_id {
+                player_stats.kills += 1;
             }
         }
     }
@@ -519,23 +466,42 @@ fn make_match_summary_with_full_squad(all_squads: &mut HashMap<i32, Vec<String>>
         squad_match_data.insert("date".to_string(), json!(date));
         squad_match_data.insert("game_mode".to_string(), json!(game_mode));
         squad_match_data.insert("map_name".to_string(), json!(actual_map_name));
+        squad_match_data.insert("shard".to_string(), json!(shard));
+        squad_match_data.insert("match_type".to_string(), json!(match_attributes.match_type));
+        squad_match_data.insert("ranked".to_string(), json!(match_attributes.match_type.as_deref() == Some(ranked::RANKED_MATCH_TYPE)));
         squad_match_data.insert("squad".to_string(), json!(squad));
 
-   let save_result = save_to_match(squad_match_data ,id)?;
-   if !save_result {
-       return Ok(false)
-   }
-   Ok(true)
+    squad_match_data
+}
+
+fn match_date(summary: &HashMap<String, Value>) -> &str {
+    summary.get("date").and_then(Value::as_str).unwrap_or_default()
 }
 
-fn save_to_match(new_match: HashMap<String, Value>, id: &String) -> Result<bool, Box<dyn std::error::Error>> {
+/// What `save_to_match` did with a summary.
+#[derive(Debug, PartialEq)]
+enum SavedSummary {
+    Stored,
+    /// Already in `matches.json` and not replaced.
+    AlreadyStored,
+    /// Older than every stored match while the list is full, so it would be
+    /// trimmed right away. Nothing was written.
+    TooOld,
+}
+
+/// Adds the summary to `data/matches.json`, which is kept oldest first by
+/// `date`. An already stored match is only overwritten, in place, when
+/// `replace_existing` is set.
+fn save_to_match(new_match: HashMap<String, Value>, id: &String, replace_existing: bool) -> Result<SavedSummary, FetchError> {
     let mut existing_data: Vec<HashMap<String, Value>> = Vec::new();
 
-    if let Ok(mut file) = File::open("data/matches.json") {
+    if let Ok(mut file) = File::open(data_path("matches.json")) {
           let mut contents = String::new();
-          file.read_to_string(&mut contents)?;
-          existing_data = from_str(&contents)?;
+          file.read_to_string(&mut contents).map_err(|e| FetchError::storage("reading data/matches.json", e))?;
+          existing_data = from_str(&contents).map_err(|e| FetchError::json("data/matches.json", e))?;
       }
+    // vanhin ensimmäisenä, vanhempi versio ei pitänyt järjestystä
+    existing_data.sort_by(|a, b| match_date(a).cmp(match_date(b)));
 
       let ids: Vec<String> = existing_data.iter()
             .filter_map(|single_match| {
@@ -543,173 +509,128 @@ fn save_to_match(new_match: HashMap<String, Value>, id: &String) -> Result<bool,
             })
             .collect();
 
-    if !ids.is_empty() {
-         if ids.contains(&id) {
-             return Ok(false) // nein new matches
+    if let Some(position) = ids.iter().position(|stored_id| stored_id == id) {
+        if !replace_existing {
+            return Ok(SavedSummary::AlreadyStored) // nein new matches
         }
+        existing_data[position] = new_match;
+    } else {
+        // uudelleen yritetty peli voi olla vanhempi kuin jo tallennetut, se lisätään päivämäärän mukaiseen kohtaan
+        let position = existing_data.partition_point(|stored| match_date(stored) <= match_date(&new_match));
+        if position == 0 && existing_data.len() >= MAX_STORED_MATCHES {
+            return Ok(SavedSummary::TooOld);
+        }
+        existing_data.insert(position, new_match);
     }
 
-    existing_data.push(new_match);
-
-    while existing_data.len() > 30 {
+    let mut trimmed: Vec<String> = Vec::new();
+    while existing_data.len() > MAX_STORED_MATCHES {
         let removed_match = existing_data.remove(0);
-        if let Some(first_match) = existing_data.get(0) {
-            if let Some(match_id) = first_match.get("id") {
-                if let Some(id) = match_id.as_str() {
-                    delete_single_match_from_redis(id);
-                }
+        if let Some(id) = removed_match.get("id").and_then(|match_id| match_id.as_str()) {
+            let _ = delete_single_match_from_redis(id);
+            // reitit ja alueet näytetään vain matches.json:n matseille
+            for folder in ["paths", "zones"] {
+                let _ = std::fs::remove_file(data_path(&format!("{}/{}.json", folder, id)));
             }
+            trimmed.push(id.to_string());
         }
     }
+    if let Err(e) = landing::forget(&trimmed) {
+        eprintln!("Failed to drop the landings of trimmed matches: {}", e);
+    }
 
-    let json_data = serde_json::to_string_pretty(&existing_data)?;
-    let folder_path = "data";
-    let file_path = format!("{}/matches.json", folder_path);
-    std::fs::create_dir_all(folder_path)?;
-
-    let mut file = File::create(file_path)?;
-    file.write_all(json_data.as_bytes())?;
+    let json_data = serde_json::to_string_pretty(&existing_data).map_err(|e| FetchError::json("match summaries", e))?;
+    write_atomic(&data_path("matches.json"), json_data.as_bytes())?;
     println!("success");
-    let _ = load_matches_summary_data_into_redis()?;
-    Ok(true)
+    // tiedostot on tallessa, redis-palvelin lataa ne käynnistyessä jos tämä epäonnistuu
+    if let Err(e) = load_matches_summary_data_into_redis() {
+        eprintln!("Failed to update match summaries in Redis: {}", e);
+    }
+    Ok(SavedSummary::Stored)
 }
 
-async fn fetch_telemetry_data (url: String, id: &String, parsed_include_data_for_passing: MatchOverviewInclude, parsed_match_data: MatchOverview) -> Result<(), Box<dyn std::error::Error>> {
-    let client = Client::new();
-    let mut headers = header::HeaderMap::new();
-    headers.insert(header::ACCEPT, "application/vnd.api+json".parse()?);
-    headers.insert(header::ACCEPT_ENCODING, "gzip".parse()?);
-    let response = client
-        .get(url)
-        .headers(headers)
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    let parsed_for_all_events: Vec<Event> = serde_json::from_str(&response).expect("Failed to parse JSON");
-    let mut all_attack_and_take_damage_events: Vec<&mut Event> = Vec::new();
-    let mut all_armor_equip_events: Vec<&mut Event> = Vec::new();
-    let mut match_start_time = String::new();
-    let mut all_squads: HashMap<i32, Vec<String>> = HashMap::new();
-
-    for event in &parsed_for_all_events {
-        match event.action {
-            Some(ActionType::LogPlayerCreate) => {
-                if let Some(player) = &event.player {
-                    if let (Some(name), Some(team_id)) = (Some(&player.name), Some(&player.team_id)) {
-                        all_squads.entry(*team_id)
-                            .or_insert_with(Vec::new)
-                            .push(name.to_string());
-                    }
-                }
-            }
-            Some(ActionType::LogMatchDefinition) => {
-                if let Some(time) = &event.event_time {
-                    match_start_time = time.to_string();
-                }
-            }
-            _ => {}
-        }
+/// Writes `contents` to a temporary file next to `path` and renames it over
+/// `path`, so readers never see a half written file.
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), FetchError> {
+    let tmp_path = path.with_extension("tmp");
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| FetchError::storage(format!("creating {}", parent.display()), e))?;
     }
+    let mut file = File::create(&tmp_path).map_err(|e| FetchError::storage(format!("creating {}", tmp_path.display()), e))?;
+    file.write_all(contents).map_err(|e| FetchError::storage(format!("writing {}", tmp_path.display()), e))?;
+    std::fs::rename(&tmp_path, path).map_err(|e| FetchError::storage(format!("replacing {}", path.display()), e))
+}
 
-    let new_match_found = make_match_summary_with_full_squad(&mut all_squads, parsed_include_data_for_passing, parsed_match_data, id, parsed_for_all_events.clone())?;
+fn parse_time(value: &str) -> Result<DateTime<FixedOffset>, FetchError> {
+    DateTime::parse_from_rfc3339(value).map_err(|e| FetchError::timestamp(value, e))
+}
 
-    if !new_match_found {
-        println!("No new matches found");
-        return Ok(());
-    }
-    println!("Continuing, {:?}", new_match_found);
+/// A fully enriched match that has not been written anywhere yet.
+struct EnrichedMatch {
+    id: String,
+    summary: HashMap<String, Value>,
+    events: Vec<Event>,
+    paths: movement::MatchPaths,
+    landing: Option<landing::MatchLanding>,
+    zones: zones::MatchZones,
+}
 
+async fn fetch_telemetry_data (url: String, id: &String, shard: &str, telemetry_source: &TelemetrySource, participants: Vec<ParticipantAttributes>, match_attributes: MatchOverviewAttributes) -> Result<EnrichedMatch, FetchError> {
+    if url.is_empty() {
+        return Err(FetchError::MissingTelemetry { match_id: id.to_string(), detail: "match response has no telemetry asset".to_string() });
+    }
 
-    let mut filter_parsed_for_all_events_without_unknown_events: Vec<Event> = parsed_for_all_events
-        .into_iter()
-        .filter(|event| !matches!(event.action, Some(ActionType::Unknown)))
-        .collect();
+    // lataus on async, jäsennys ja rikastus ovat raskasta laskentaa eivätkä saa jumittaa runtimea
+    let downloaded = match telemetry_source {
+        TelemetrySource::Api => Some(telemetry::download_to_temp_file(&url, id).await?),
+        _ => None,
+    };
+    let (match_id, shard, telemetry_source) = (id.clone(), shard.to_string(), telemetry_source.clone());
+    tokio::task::spawn_blocking(move || enrich_telemetry(&url, &match_id, &shard, &telemetry_source, downloaded, participants, match_attributes))
+        .await
+        .map_err(|e| FetchError::task(format!("enrichment of match {}", id), e))?
+}
 
-    for event in filter_parsed_for_all_events_without_unknown_events.iter_mut() {
-        match event.action {
-            Some(ActionType::LogPlayerAttack) | Some(ActionType::LogPlayerTakeDamage) => {
-                all_attack_and_take_damage_events.push(event);
-            }
-            Some(ActionType::LogItemEquip) | Some(ActionType::LogArmorDestroy) | Some(ActionType::LogItemPickupFromCarepackage) | Some(ActionType::LogItemPickupFromLootbox) => {
-                if let Some(ref mut armor) = event.item {
-                    if let Some((_, new_armor_name)) = constants::armors::ARMORS
-                        .iter()
-                        .find(|&&(key, _)| key == armor.item_id)
-                    {
-                        armor.item_id = new_armor_name.to_string();
-                        all_armor_equip_events.push(event);
-                    }
-                }
-            }
-            _ => {}
+/// The blocking half of `fetch_telemetry_data`. `downloaded` is the telemetry
+/// fetched from the API, without it the telemetry is read from `telemetry_source`.
+fn enrich_telemetry (url: &str, id: &String, shard: &str, telemetry_source: &TelemetrySource, downloaded: Option<PathBuf>, participants: Vec<ParticipantAttributes>, match_attributes: MatchOverviewAttributes) -> Result<EnrichedMatch, FetchError> {
+    let tracked = TrackedPlayers::load();
+    let mut collector = TelemetryCollector::new(tracked.clone());
+    match (telemetry_source, downloaded) {
+        (_, Some(path)) => {
+            archive_telemetry(id, File::open(&path));
+            let parsed = File::open(&path)
+                .map_err(|e| FetchError::storage(format!("reading {}", path.display()), e))
+                .and_then(|file| telemetry::stream_events(BufReader::new(file), id, &mut collector));
+            let _ = std::fs::remove_file(&path);
+            parsed?;
+        }
+        (TelemetrySource::Directory(dir), None) => {
+            archive_telemetry(id, offline::open_local_telemetry(dir, id, url));
+            telemetry::stream_events(offline::open_local_telemetry(dir, id, url)?, id, &mut collector)?;
+        }
+        (TelemetrySource::Api | TelemetrySource::Archive, None) => {
+            telemetry::stream_events(archive::open_telemetry(id)?, id, &mut collector)?;
         }
     }
 
-    let parsed: Vec<Event> = serde_json::from_str(&response).expect("Failed to parse JSON");
+    let TelemetryCollector { squads, match_start_time, finisher_kills, armor_equips, tracked_events, mut attacks, unknown, movement, landings, zones, .. } = collector;
+    if let Err(e) = telemetry::report_unknown_events(id, &unknown) {
+        eprintln!("Failed to record unknown telemetry events of {}: {}", id, e);
+    }
 
-    let mut filtered_events: Vec<Event> = parsed
-        .into_iter()
-        .filter(|event| !matches!(event.action, Some(ActionType::Unknown)))
-        .collect();
+    let summary = make_match_summary_with_full_squad(&squads, participants, match_attributes, id, shard, &finisher_kills, &tracked);
+    // sama kartan nimi kuin matches.json:ssa ja landings.json:ssa
+    let map_name = summary.get("map_name").and_then(Value::as_str).unwrap_or_default();
+    let paths = movement.into_paths(id, map_name, &match_start_time);
+    let zones = zones.analyze(id, map_name, &match_start_time);
+    let landing = landings.analyze(id, &summary, &match_start_time, &squads, &tracked);
 
-    let mut take_damage_events: Vec<&mut Event> = Vec::new();
-    let mut attack_events: Vec<&mut Event> = Vec::new();
-
-    for event in filtered_events.iter_mut() {
-        match event.action {
-            Some(ActionType::LogPlayerTakeDamage) | Some(ActionType::LogArmorDestroy) => {
-                if constants::player_names::PLAYER_NAMES.iter().any(|&username| {
-                    match (&event.attacker, &event.victim) {
-                        (Some(attacker), _) if attacker.name == username => true,
-                        (_, Some(victim)) if victim.name == username => true,
-                        _ => false,
-                    }
-                }) {
-                    match event.action {
-                        Some(ActionType::LogPlayerTakeDamage) => take_damage_events.push(event),
-                        Some(ActionType::LogArmorDestroy) => take_damage_events.push(event),
-                        _ => {}
-                    }
-                }
-            }
-            Some(ActionType::LogPlayerKillV2) => {
-                if constants::player_names::PLAYER_NAMES.iter().any(|&username| {
-                    if let Some(attacker) = &event.attacker {
-                        attacker.name == username
-                    } else if let Some(victim) = &event.victim {
-                        victim.name == username
-                    } else if let Some(dbno_maker) = &event.dbno_maker {
-                        dbno_maker.name == username
-                    } else if let Some(finisher) = &event.finisher {
-                        finisher.name == username
-                    } else if let Some(killer) = &event.killer {
-                        killer.name == username
-                    }
-                    else {
-                        false
-                    }
-                }) {
-                    match event.action {
-                        Some(ActionType::LogPlayerKillV2) => take_damage_events.push(event),
-                        _ => {}
-                    }
-                }
-            }
-            Some(ActionType::LogPlayerAttack) => {
-                attack_events.push(event);
-            }
-            _ => {}
-        }
+    let mut take_damage_events: Vec<Event> = tracked_events;
+    for event in take_damage_events.iter_mut() {
+        use_current_names(event, &tracked);
     }
 
-    let mut attack_ids: HashSet<i32> = attack_events
-            .iter()
-            .filter_map(|event| event.attack_id)
-            .collect();
-    attack_ids.insert(-1);
-
     for event in take_damage_events.iter_mut() {
         event.helmet = Some(Armor {
             condition: true,
@@ -734,123 +655,113 @@ async fn fetch_telemetry_data (url: String, id: &String, parsed_include_data_for
         event.bullet_speed = Some(0.0);
 
         if let Some(victim) = &event.victim {
-            for action_damage_event in &all_attack_and_take_damage_events {
-                if Some(action_damage_event.attack_id) == Some(event.attack_id) {
-                    if event.attack_id == Some(-1) {continue}
-
-                    for equip_event in &all_armor_equip_events {
+            let times = event.attack_id.filter(|attack_id| *attack_id != -1).and_then(|attack_id| attacks.get(&attack_id)).map(|attack| &attack.times);
+            for damage_time in times.into_iter().flatten() {
+                for equip_event in &armor_equips {
 
-                        if let (Some(attack_time_str), Some(equip_time_str)) = (&event.event_time, &equip_event.event_time) {
-                            let attack_time = DateTime::parse_from_rfc3339(attack_time_str).expect("Invalid time");
-                            let equip_time = DateTime::parse_from_rfc3339(equip_time_str).expect("Invalid time");
+                    if let (Some(attack_time_str), Some(equip_time_str)) = (&event.event_time, &equip_event.event_time) {
+                        let attack_time = parse_time(attack_time_str)?;
+                        let equip_time = parse_time(equip_time_str)?;
 
-                            if attack_time < equip_time {
-                                continue;
-                            }
+                        if attack_time < equip_time {
+                            continue;
+                        }
 
-                             if let Some(action) = &equip_event.action {
-                                 match action {
-                                     ActionType::LogItemEquip |
-                                     ActionType::LogItemPickupFromCarepackage |
-                                     ActionType::LogItemPickupFromLootbox => {
-                                         if let (Some(attacker), Some(equiper)) = (&event.attacker, &equip_event.player) {
-                                             if attacker.name == equiper.name {
-                                                 if let Some(item) = &equip_event.item {
-                                                     if item.item_id.contains("Helmet") {
-                                                         if let Some(helmet) = event.helmet.as_mut() {
-                                                             helmet.item = item.item_id.clone();
-                                                             helmet.condition = true;
-                                                         }
-                                                   }
-
-                                                     if item.item_id.contains("Vest") {
-                                                         if let Some(vest) = event.vest.as_mut() {
-                                                             vest.item = item.item_id.clone();
-                                                             vest.condition = true;
-                                                         }
-                                                    }
-                                                 }
+                         if let Some(ActionType::LogItemEquip |
+                                 ActionType::LogItemPickupFromCarepackage |
+                                 ActionType::LogItemPickupFromLootbox) = &equip_event.action {
+                                     if let (Some(attacker), Some(equiper)) = (&event.attacker, &equip_event.player) {
+                                         if attacker.account_id == equiper.account_id {
+                                             if let Some(item) = &equip_event.item {
+                                                 if item.item_id.contains("Helmet") {
+                                                     if let Some(helmet) = event.helmet.as_mut() {
+                                                         helmet.item = item.item_id.clone();
+                                                         helmet.condition = true;
+                                                     }
+                                               }
+
+                                                 if item.item_id.contains("Vest") {
+                                                     if let Some(vest) = event.vest.as_mut() {
+                                                         vest.item = item.item_id.clone();
+                                                         vest.condition = true;
+                                                     }
+                                                }
                                              }
+                                         }
 
-                                             if victim.name == equiper.name {
-                                                 if let Some(item) = &equip_event.item {
-                                                     if item.item_id.contains("Helmet") {
-                                                         if let Some(victim_helmet) = event.victim_helmet.as_mut() {
-                                                             victim_helmet.item = item.item_id.clone();
-                                                             victim_helmet.condition = true;
-                                                        }
-                                                   }
-
-                                                    if item.item_id.contains("Vest") {
-                                                         if let Some(victim_vest) = event.victim_vest.as_mut() {
-                                                             victim_vest.item = item.item_id.clone();
-                                                             victim_vest.condition = true;
-                                                         }
+                                         if victim.account_id == equiper.account_id {
+                                             if let Some(item) = &equip_event.item {
+                                                 if item.item_id.contains("Helmet") {
+                                                     if let Some(victim_helmet) = event.victim_helmet.as_mut() {
+                                                         victim_helmet.item = item.item_id.clone();
+                                                         victim_helmet.condition = true;
                                                     }
-                                                 }
+                                               }
+
+                                                if item.item_id.contains("Vest") {
+                                                     if let Some(victim_vest) = event.victim_vest.as_mut() {
+                                                         victim_vest.item = item.item_id.clone();
+                                                         victim_vest.condition = true;
+                                                     }
+                                                }
                                              }
                                          }
-                                     },
-                                     _ => {}
+                                     }
                                  }
-                             }
-                        }
                     }
+                }
 
-                    if let Some(finisher) = &event.finisher {
-                        let start = Location { x: finisher.location.x, y: finisher.location.y, z: finisher.location.z };
+                if let Some(finisher) = &event.finisher {
+                    let start = Location { x: finisher.location.x, y: finisher.location.y, z: finisher.location.z };
+                    let end = Location { x: victim.location.x, y: victim.location.y, z: victim.location.z };
+                    let distance = start.distance(&end);
+                    event.distance = Some(distance);
+                }
+
+                if let (Some(attack_time), Some(victim_time)) = (&event.event_time, damage_time) {
+                    let attack_time = parse_time(attack_time)?;
+                    let victim_time = parse_time(victim_time)?;
+                    let duration = victim_time.signed_duration_since(attack_time);
+                    let travel_time = (duration.num_milliseconds() as f32 / 1000.0).abs();
+                    if travel_time == 0.0 {continue}
+                    if let Some(attacker) = &event.attacker {
+                        let start = Location { x: attacker.location.x, y: attacker.location.y, z: attacker.location.z };
                         let end = Location { x: victim.location.x, y: victim.location.y, z: victim.location.z };
                         let distance = start.distance(&end);
+                        if distance == 0.0 {continue}
                         event.distance = Some(distance);
-                    }
-
-                    if let (Some(attack_time), Some(victim_time)) = (&event.event_time, &action_damage_event.event_time) {
-                        let attack_time = DateTime::parse_from_rfc3339(attack_time)
-                            .expect("Invalid attack time");
-                        let victim_time = DateTime::parse_from_rfc3339(victim_time)
-                            .expect("Invalid victim time");
-                        let duration = victim_time.signed_duration_since(attack_time);
-                        let travel_time = (duration.num_milliseconds() as f32 / 1000.0).abs();
-                        if travel_time == 0.0 {continue}
-                        if let Some(attacker) = &event.attacker {
-                            let start = Location { x: attacker.location.x, y: attacker.location.y, z: attacker.location.z };
-                            let end = Location { x: victim.location.x, y: victim.location.y, z: victim.location.z };
-                            let distance = start.distance(&end);
-                            if distance == 0.0 {continue}
-                            event.distance = Some(distance);
-                            let bullet_speed = distance / travel_time;
-                            let current_weapon = &event.damage_causer_name;
-                            let formated_current_weapon = constants::weapons::WEAPONS
-                                      .iter()
-                                      .find(|&&(key, _)| key == current_weapon.clone().expect("Weapon not found").to_string())
-                                      .map(|&(_, weapon_name)| weapon_name.to_string())
-                                      .unwrap_or_else(|| "Unknown".to_string());
-
-                            let weapon_type = constants::weapon_type::WEAPON_TYPE
+                        let bullet_speed = distance / travel_time;
+                        let current_weapon = &event.damage_causer_name;
+                        let formated_current_weapon = constants::weapons::WEAPONS
+                                  .iter()
+                                  .find(|&&(key, _)| Some(key) == current_weapon.as_deref())
+                                  .map(|&(_, weapon_name)| weapon_name.to_string())
+                                  .unwrap_or_else(|| "Unknown".to_string());
+
+                        let weapon_type = constants::weapon_type::WEAPON_TYPE
+                            .iter()
+                            .find(|&&(key, _)| key == formated_current_weapon.as_str())
+                            .map(|&(_, weapon_type)| weapon_type.to_string())
+                            .unwrap_or_else(|| "Unknown".to_string());
+
+                        let max_bullet_speed = match weapon_type.as_str() {
+                            "SMG" | "Pistol" => 500.0,
+                            "DMR" | "LMG" | "Assault Rifle" => 1000.0,
+                            "HP Sniper" => 1500.0,
+                            "Shotgun" => 700.0,
+                            _ => f32::MAX,
+                        };
+
+                        if bullet_speed > max_bullet_speed {
+                            let default_bullet_speed = constants::default_bullet_speed::DEFAULT_BULLET_SPEED
                                 .iter()
-                                .find(|&&(key, _)| key == formated_current_weapon.as_str())
-                                .map(|&(_, weapon_type)| weapon_type.to_string())
-                                .unwrap_or_else(|| "Unknown".to_string());
-
-                            let max_bullet_speed = match weapon_type.as_str() {
-                                "SMG" | "Pistol" => 500.0,
-                                "DMR" | "LMG" | "Assault Rifle" => 1000.0,
-                                "HP Sniper" => 1500.0,
-                                "Shotgun" => 700.0,
-                                _ => f32::MAX,
-                            };
-
-                            if bullet_speed > max_bullet_speed {
-                                let default_bullet_speed = constants::default_bullet_speed::DEFAULT_BULLET_SPEED
-                                    .iter()
-                                    .find(|&&(key, _)| key == formated_current_weapon)
-                                    .and_then(|&(_, speed_str)| speed_str.parse::<f64>().ok())
-                                    .map(|value| value as f32);
-
-                                event.bullet_speed = default_bullet_speed;
-                            } else {
-                                event.bullet_speed = Some(bullet_speed);
-                            }
+                                .find(|&&(key, _)| key == formated_current_weapon)
+                                .and_then(|&(_, speed_str)| speed_str.parse::<f64>().ok())
+                                .map(|value| value as f32);
+
+                            event.bullet_speed = default_bullet_speed;
+                        } else {
+                            event.bullet_speed = Some(bullet_speed);
                         }
                     }
                 }
@@ -859,11 +770,11 @@ async fn fetch_telemetry_data (url: String, id: &String, parsed_include_data_for
     }
 
     for event in take_damage_events.iter_mut() {
-        if !event.damage_causer_name.is_none() { // tää voi olla että sun setataan kohta muutenkin
+        if event.damage_causer_name.is_some() { // tää voi olla että sun setataan kohta muutenkin
             let current_weapon = &event.damage_causer_name;
             let formatted_name = constants::weapons::WEAPONS
                 .iter()
-                .find(|&&(key, _)| key == &current_weapon.clone().expect("REASON").to_string())
+                .find(|&&(key, _)| Some(key) == current_weapon.as_deref())
                 .map(|&(_, name)| name.to_string())
                 .unwrap_or_else(|| "Unknown".to_string());
             event.damage_causer_name = Some(formatted_name);
@@ -896,7 +807,7 @@ async fn fetch_telemetry_data (url: String, id: &String, parsed_include_data_for
         */
 
         if let Some(ref mut damage_type_category) = event.damage_type_category {
-            if let Some(&(_, ref new_formated_type)) = constants::damage_types::DAMAGE_TYPES
+            if let Some((_, new_formated_type)) = constants::damage_types::DAMAGE_TYPES
                 .iter()
                 .find(|&&(key, _)| key == damage_type_category)
             {
@@ -917,88 +828,118 @@ async fn fetch_telemetry_data (url: String, id: &String, parsed_include_data_for
     for event in take_damage_events.iter_mut() {
 
         if let Some(event_time) = &event.event_time {
-            let parsed_event_time = DateTime::parse_from_rfc3339(event_time).expect("Invalid event time");
-            let start_time = DateTime::parse_from_rfc3339(&match_start_time).expect("Invalid match start time");
+            let parsed_event_time = parse_time(event_time)?;
+            let start_time = parse_time(&match_start_time)?;
             let time = parsed_event_time.signed_duration_since(start_time);
             let time_formatted = time.num_milliseconds() as f32 / 1000.0;
             event.event_time = Some(time_formatted.to_string());
         }
 
         if let Some(attack_id) = event.attack_id {
-            if attack_ids.contains(&attack_id) {
-                if let Some(attack_event) = attack_events.iter_mut().find(|e| e.attack_id == Some(attack_id)) {
-                    if event.weapon.is_none() {
-                        if let Some(ref mut weapon) = attack_event.weapon {
-                            for attachment in &mut weapon.attachments {
-                                if let Some(&(_, ref new_name)) = constants::attachments::ATTACHMENTS
-                                    .iter()
-                                    .find(|&&(key, _)| key == *attachment)
-                                {
-                                    *attachment = new_name.to_string().clone();
-                                } else {
-                               //     println!("{:?}", attachment);
-                                }
+            if let Some(attack_event) = attacks.get_mut(&attack_id) {
+                if event.weapon.is_none() {
+                    if let Some(ref mut weapon) = attack_event.weapon {
+                        for attachment in &mut weapon.attachments {
+                            if let Some((_, new_name)) = constants::attachments::ATTACHMENTS
+                                .iter()
+                                .find(|&&(key, _)| key == *attachment)
+                            {
+                                *attachment = new_name.to_string().clone();
+                            } else {
+                           //     println!("{:?}", attachment);
                             }
-                        if let Some(&(_, ref new_weapon_name)) = constants::weapons::WEAPONS
-                                  .iter()
-                                  .find(|&&(key, _)| key == weapon.weapon)
-                              {
-                                  weapon.weapon = new_weapon_name.to_string();
-                              } else {
-                                  weapon.weapon = weapon.weapon.to_string();
-                              }
-                            event.weapon = Some(weapon.clone());
                         }
+                    if let Some((_, new_weapon_name)) = constants::weapons::WEAPONS
+                              .iter()
+                              .find(|&&(key, _)| key == weapon.weapon)
+                          {
+                              weapon.weapon = new_weapon_name.to_string();
+                          } else {
+                              weapon.weapon = weapon.weapon.to_string();
+                          }
+                        event.weapon = Some(weapon.clone());
                     }
+                }
 
-                    if event.fire_weapon_stack_count.is_none() {
-                        event.fire_weapon_stack_count = attack_event.fire_weapon_stack_count.clone();
-                    }
+                if event.fire_weapon_stack_count.is_none() {
+                    event.fire_weapon_stack_count = attack_event.fire_weapon_stack_count;
                 }
             }
         }
     }
 
-    let filename: &str = &id;
-    let _ = save_to_json(&take_damage_events, filename).await;
-    Ok(())
+    Ok(EnrichedMatch { id: id.to_string(), summary, events: take_damage_events, paths, landing, zones })
 }
 
-async fn save_to_json(data: &Vec<& mut Event>, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let json_data = serde_json::to_string_pretty(&data)?;
+/// Writes the summary and the events of an enriched match to disk and Redis.
+/// `save_to_match` keeps the summaries in date order, so the trimming
+/// always drops the oldest matches.
+async fn store_enriched_match(enriched: EnrichedMatch, replace_existing: bool) -> Result<(), FetchError> {
+    let id = &enriched.id;
+    match save_to_match(enriched.summary, id, replace_existing)? {
+        SavedSummary::Stored => {}
+        SavedSummary::AlreadyStored => {
+            // edellinen yritys kaatui summaryn tallennuksen jälkeen, telemetria puuttuu vielä
+            println!("Summary for {} already stored, rebuilding match events", id);
+        }
+        SavedSummary::TooOld => {
+            println!("Match {} is older than the {} stored matches, not storing it", id, MAX_STORED_MATCHES);
+            return Ok(());
+        }
+    }
 
-    let folder_path = "data/matches";
-    let file_path = format!("{}/{}.json", folder_path, filename);
+    save_to_json(&enriched.events, id).await?;
+    movement::save(&enriched.paths)?;
+    zones::save(&enriched.zones)?;
+    match &enriched.landing {
+        Some(landing) => landing::record(landing),
+        None => Ok(()),
+    }
+}
 
-    let _ = std::fs::create_dir_all(folder_path)?;
+async fn save_to_json(data: &[Event], filename: &str) -> Result<(), FetchError> {
+    let json_data = serde_json::to_string_pretty(&data).map_err(|e| FetchError::json(format!("events of match {}", filename), e))?;
 
-    let mut file = File::create(file_path)?;
-    file.write_all(json_data.as_bytes())?;
-    let _ = load_single_match_to_redis(filename)?;
+    write_atomic(&data_path(&format!("matches/{}.json", filename)), json_data.as_bytes())?;
+    if let Err(e) = load_single_match_to_redis(filename) {
+        eprintln!("Failed to load match {} into Redis: {}", filename, e);
+    }
     Ok(())
 }
 
-async fn fetch_match_data (api_key: String, match_id: &String) -> Result<(), Box<dyn std::error::Error>>  {
-    let url = "https://api.pubg.com/shards/steam/matches/".to_owned() + &match_id;
-    let client = Client::new();
-    let headers = make_headers(&api_key)?;
-
-    let response = client
-        .get(url)
-        .headers(headers)
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    let parsed_match_data: MatchOverview = serde_json::from_str(&response).expect("Failed to parse JSON");
-    let parsed_include_data: MatchOverviewInclude = serde_json::from_str(&response).expect("Failed to parse JSON");
-    let parsed_include_data_for_passing: MatchOverviewInclude = serde_json::from_str(&response).expect("Failed to parse JSON");
+async fn fetch_match_response (pubg_client: &PubgClient, shard: &str, match_id: &str) -> Result<String, FetchError>  {
+    let url = pubg_client.shard_url(shard, &format!("matches/{}", match_id));
+    pubg_client.get(&url).await
+}
+
+// arkistointi ei saa kaataa ingestiä, puuttuva arkisto vain lokitetaan
+fn archive_telemetry<R: Read, E: std::fmt::Display>(match_id: &str, reader: Result<R, E>) {
+    let archived = match reader {
+        Ok(reader) => archive::store_telemetry(match_id, reader).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = archived {
+        eprintln!("Failed to archive telemetry of match {}: {}", match_id, e);
+    }
+}
+
+fn archive_match_response(match_response: &MatchResponse, shard: &str, raw: &str) {
+    let data = &match_response.data;
+    if let Err(e) = archive::store_match_response(&data.id, shard, &data.attributes.created_at, raw) {
+        eprintln!("Failed to archive match {}: {}", data.id, e);
+    }
+}
+
+fn parse_match_response(response: &str) -> Result<MatchResponse, FetchError> {
+    serde_json::from_str(response).map_err(|e| FetchError::json("match response", e))
+}
+
+/// Downloads the telemetry and runs the enrichment, without storing anything.
+async fn enrich_match (match_response: MatchResponse, shard: &str, telemetry_source: &TelemetrySource) -> Result<EnrichedMatch, FetchError>  {
     let mut telemetry_url = String::new();
     let mut single_player_performance: Vec<ParticipantAttributes> = Vec::new();
-    let mut rosters: Vec<RosterAttributes> = Vec::new(); //rank: 13, team_id: 16 en tiiä mihin näitä tarvis.. telemtry datasta saa vasta squadin.............. teamid kaikille
 
-    for item in parsed_include_data.included {
+    for item in match_response.included {
         match item {
             MatchOverviewIncluded::Asset { attributes, .. } => {
                    telemetry_url = attributes.url;
@@ -1007,64 +948,308 @@ async fn fetch_match_data (api_key: String, match_id: &String) -> Result<(), Box
                 single_player_performance.push(attributes)
 
             }
-            MatchOverviewIncluded::Roster {attributes, ..} => {
-                rosters.push(attributes);
-            }
+            MatchOverviewIncluded::Roster => {}
             //_ => {}
         }
     }
-    let id = &parsed_match_data.data.id.clone();
+    let id = &match_response.data.id;
 
-    let _ = fetch_telemetry_data(telemetry_url, id, parsed_include_data_for_passing, parsed_match_data).await?;
+    fetch_telemetry_data(telemetry_url, id, shard, telemetry_source, single_player_performance, match_response.data.attributes).await
+}
 
-    Ok(())
+async fn fetch_match_data (match_response: MatchResponse, shard: &str, telemetry_source: &TelemetrySource) -> Result<(), FetchError>  {
+    let enriched = enrich_match(match_response, shard, telemetry_source).await?;
+    // reprocess korvaa jo tallennetun matsin
+    store_enriched_match(enriched, matches!(telemetry_source, TelemetrySource::Archive)).await
 }
 
-async fn fetch_player_match_ids(api_key: String) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let player_ids_url = make_player_id_url();
-    let client = Client::new();
+#[derive(Debug, Clone)]
+struct DiscoveredMatch {
+    id: String,
+    shard: String,
+}
 
-    let mut headers = header::HeaderMap::new();
-    headers.insert(header::ACCEPT, "application/vnd.api+json".parse()?);
-    headers.insert(header::AUTHORIZATION, format!("Bearer {}", api_key).parse()?);
+struct PendingMatch {
+    id: String,
+    shard: String,
+    response: MatchResponse,
+}
 
-    let response = client
-          .get(player_ids_url)
-          .headers(headers)
-          .send()
-          .await?
-          .text()
-          .await?;
+async fn fetch_player_match_ids(pubg_client: &PubgClient) -> Result<Vec<DiscoveredMatch>, FetchError> {
+    let mut discovered: Vec<DiscoveredMatch> = Vec::new();
+    let shards = roster::shards();
+
+    for shard in &shards {
+        let player_ids_url = roster::make_player_id_url(pubg_client, shard);
+        let response = match pubg_client.get(&player_ids_url).await {
+            Ok(response) => response,
+            Err(e) if shards.len() > 1 => {
+                eprintln!("Failed to fetch match IDs for shard {}: {}", shard, e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
 
-    let match_ids = extract_user_id(&response).await;
+        for id in extract_user_id(&response, shard)? {
+            discovered.push(DiscoveredMatch { id, shard: shard.clone() });
+        }
+    }
 
-    Ok(match_ids)
+    Ok(discovered)
+}
+
+/// Ingests every discovered match that is not stored yet, oldest first.
+/// Up to `concurrency` telemetry files are downloaded and enriched at once.
+async fn ingest_new_matches(pubg_client: &PubgClient, match_ids: &[DiscoveredMatch], status: &mut IngestStatus, concurrency: usize) {
+    let mut seen: HashSet<&String> = HashSet::new();
+    let missing: Vec<&DiscoveredMatch> = match_ids
+        .iter()
+        .filter(|discovered| seen.insert(&discovered.id))
+        .filter(|discovered| status.needs_ingest(&discovered.id))
+        .collect();
+
+    if missing.is_empty() {
+        println!("No new matches found");
+        return;
+    }
+    println!("Found {} new matches", missing.len());
+
+    // match listat tulee pelaajakohtaisesti uusin ensin, järjestys haetaan createdAt:sta
+    let mut pending: Vec<PendingMatch> = Vec::new();
+    for discovered in missing {
+        let match_id = &discovered.id;
+        match fetch_match_response(pubg_client, &discovered.shard, match_id).await {
+            Ok(raw) => match parse_match_response(&raw) {
+                Ok(response) => {
+                    archive_match_response(&response, &discovered.shard, &raw);
+                    pending.push(PendingMatch {
+                        id: match_id.to_string(),
+                        shard: discovered.shard.clone(),
+                        response,
+                    })
+                }
+                Err(e) => {
+                    eprintln!("Failed to parse match {}: {}", match_id, e);
+                    status.mark_failed(match_id, e.to_string());
+                }
+            },
+            Err(e) => {
+                eprintln!("Failed to fetch match {}: {}", match_id, e);
+                status.mark_failed(match_id, e.to_string());
+            }
+        }
+    }
+    pending.sort_by(|a, b| a.response.data.attributes.created_at.cmp(&b.response.data.attributes.created_at));
+
+    // vanhemmat kuin 30 uusinta trimmattaisiin heti pois matches.json:sta
+    let skip_count = pending.len().saturating_sub(MAX_STORED_MATCHES);
+    for skipped in pending.drain(..skip_count) {
+        status.mark_skipped(&skipped.id);
+    }
+
+    // telemetriat haetaan ja rikastetaan rinnakkain, buffered palauttaa tulokset
+    // alkuperäisessä järjestyksessä joten tallennus menee aina vanhin ensin
+    let mut enriched_matches = stream::iter(pending)
+        .map(|pending_match| {
+            let task = tokio::spawn(async move {
+                enrich_match(pending_match.response, &pending_match.shard, &TelemetrySource::Api).await
+            });
+            async move { (pending_match.id, task.await) }
+        })
+        .buffered(concurrency.max(1));
+
+    let mut ranked_players: Vec<ranked::RankedMatchPlayer> = Vec::new();
+    while let Some((match_id, enriched)) = enriched_matches.next().await {
+        let stored = match enriched {
+            Ok(Ok(enriched)) => {
+                let players = ranked::tracked_players_in(&enriched.summary);
+                let stored = store_enriched_match(enriched, false).await;
+                if stored.is_ok() {
+                    ranked_players.extend(players);
+                }
+                stored
+            }
+            Ok(Err(e)) => Err(e),
+            Err(e) => Err(FetchError::task(format!("enrichment of match {}", match_id), e)),
+        };
+
+        match stored {
+            Ok(()) => status.mark_done(&match_id),
+            Err(e) => {
+                eprintln!("Failed to ingest match {}: {}", match_id, e);
+                status.mark_failed(&match_id, e.to_string());
+            }
+        }
+        if let Err(e) = status.save() {
+            eprintln!("Failed to save ingest status: {}", e);
+        }
+    }
+
+    if let Err(e) = status.save() {
+        eprintln!("Failed to save ingest status: {}", e);
+    }
+    ranked::refresh_after_matches(pubg_client, ranked_players).await;
+}
+
+/// Ingests one match by id, replacing nothing that is already stored.
+async fn fetch_single_match(pubg_client: &PubgClient, shard: &str, match_id: &str) -> Result<(), FetchError> {
+    let mut status = IngestStatus::load().map_err(|e| FetchError::storage("reading ingest status", e.to_string()))?;
+    let raw = fetch_match_response(pubg_client, shard, match_id).await?;
+    let response = parse_match_response(&raw)?;
+    archive_match_response(&response, shard, &raw);
+
+    let mut ranked_players: Vec<ranked::RankedMatchPlayer> = Vec::new();
+    let result = match enrich_match(response, shard, &TelemetrySource::Api).await {
+        Ok(enriched) => {
+            ranked_players = ranked::tracked_players_in(&enriched.summary);
+            store_enriched_match(enriched, false).await
+        }
+        Err(e) => Err(e),
+    };
+    match &result {
+        Ok(()) => status.mark_done(match_id),
+        Err(e) => status.mark_failed(match_id, e.to_string()),
+    }
+    if let Err(e) = status.save() {
+        eprintln!("Failed to save ingest status: {}", e);
+    }
+    if result.is_ok() {
+        ranked::refresh_after_matches(pubg_client, ranked_players).await;
+    }
+    result
+}
+
+/// One round of discovering match ids and ingesting the missing ones.
+async fn poll_once(pubg_client: &PubgClient, status: &mut IngestStatus, concurrency: usize) {
+    roster::reload_if_changed();
+    match fetch_player_match_ids(pubg_client).await {
+        Ok(match_ids) => {
+            println!("Checking for new matches!");
+            ingest_new_matches(pubg_client, &match_ids, status, concurrency).await;
+        },
+        Err(e) => {
+            eprintln!("Failed to fetch match IDs: {}", e);
+        }
+    }
+}
+
+fn make_pubg_client(base_url: &str) -> PubgClient {
+    let Ok(api_key) = env::var("api_key") else {
+        eprintln!("Api key not found, set api_key in the environment or .env");
+        std::process::exit(1);
+    };
+    match PubgClient::new(&api_key, base_url) {
+        Ok(pubg_client) => pubg_client,
+        Err(e) => {
+            eprintln!("Invalid api key: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn load_ingest_status() -> IngestStatus {
+    match IngestStatus::load() {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("Failed to read ingest status: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn exit_on_error<E: std::fmt::Display>(what: &str, result: Result<(), E>) {
+    if let Err(e) = result {
+        eprintln!("{} failed: {}", what, e);
+        std::process::exit(1);
+    }
 }
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    let api_key = env::var("api_key").expect("Api key not found...");
-    let mut init_done = false;
-
-      loop {
-          match fetch_player_match_ids(api_key.clone()).await {
-              Ok(match_ids) => {
-                  if init_done {
-                      println!("Checking for new matches!");
-                      let _ = fetch_match_data(api_key.clone(), &match_ids[0]).await;
-                  } else {
-                      println!("Initial run");
-                      for match_id in &match_ids {
-                          let _ = fetch_match_data(api_key.clone(), match_id).await;
-                      }
-                      init_done = true;
-                  }
-              },
-              Err(e) => {
-                  eprintln!("Failed to fetch match IDs: {}", e);
-              }
-          }
-          sleep(Duration::from_secs(30)).await;
-      }
+    let cli = Cli::parse();
+
+    shared_utils::paths::set_data_dir(&cli.data_dir);
+    shared_utils::redis_loader::set_redis_url(&cli.redis_url);
+    landing::set_hot_drop_radius(cli.hot_drop_radius);
+
+    if let Err(e) = roster::init().and_then(|_| roster::set_shard_filter(cli.shard.clone())) {
+        eprintln!("Invalid player roster: {}", e);
+        std::process::exit(1);
+    }
+
+    match cli.command {
+        Command::Poll { interval, idle_interval, jitter, quiet_hours, season_interval, ingest } => {
+            let pubg_client = make_pubg_client(&cli.api_base_url);
+            let mut status = load_ingest_status();
+            let mut scheduler = Scheduler::new(
+                Duration::from_secs(interval),
+                idle_interval.map(Duration::from_secs),
+                Duration::from_secs(jitter),
+                quiet_hours,
+            );
+            let mut season_refreshed: Option<Instant> = None;
+            loop {
+                if let Some(remaining) = scheduler.quiet_remaining(Local::now()) {
+                    println!("Quiet hours, next poll in {} minutes", remaining.as_secs().div_ceil(60));
+                    sleep(remaining).await;
+                    continue;
+                }
+                poll_once(&pubg_client, &mut status, ingest.concurrency).await;
+                if season_interval > 0 && season_refreshed.is_none_or(|at| at.elapsed() >= Duration::from_secs(season_interval)) {
+                    season_refreshed = Some(Instant::now());
+                    if let Err(e) = season_data::fetch_player_url(&pubg_client, None).await {
+                        eprintln!("Refreshing season stats failed: {}", e);
+                    }
+                    if let Err(e) = season_data::fetch_lifetime(&pubg_client).await {
+                        eprintln!("Refreshing lifetime stats failed: {}", e);
+                    }
+                }
+                sleep(scheduler.next_delay()).await;
+            }
+        }
+        Command::Backfill { ingest } => {
+            let pubg_client = make_pubg_client(&cli.api_base_url);
+            let mut status = load_ingest_status();
+            poll_once(&pubg_client, &mut status, ingest.concurrency).await;
+        }
+        Command::Fetch { match_id } => {
+            let pubg_client = make_pubg_client(&cli.api_base_url);
+            let shard = cli.shard.as_deref().unwrap_or(roster::DEFAULT_SHARD);
+            exit_on_error("Fetching the match", fetch_single_match(&pubg_client, shard, &match_id).await);
+        }
+        Command::Season { season_id, list, refresh } => {
+            // vanha .env asetti kauden, nyt se annetaan vain --season lipulla
+            if env::var("season_id").is_ok() {
+                eprintln!("season_id in the environment is ignored, use --season to pick a past season");
+            }
+            let pubg_client = make_pubg_client(&cli.api_base_url);
+            if list {
+                exit_on_error("Listing seasons", cli::list_seasons(&pubg_client, refresh).await);
+            } else {
+                if refresh {
+                    for shard in roster::shards() {
+                        exit_on_error("Refreshing seasons", seasons::list(&pubg_client, &shard, true).await.map(|_| ()));
+                    }
+                }
+                exit_on_error("Fetching season stats", season_data::fetch_player_url(&pubg_client, season_id.as_deref()).await);
+            }
+        }
+        Command::Lifetime => {
+            let pubg_client = make_pubg_client(&cli.api_base_url);
+            exit_on_error("Fetching lifetime stats", season_data::fetch_lifetime(&pubg_client).await);
+        }
+        Command::Reprocess { match_id, all: _ } => {
+            let selection = match match_id {
+                Some(match_id) => reprocess::Selection::Match(match_id),
+                None => reprocess::Selection::All,
+            };
+            exit_on_error("Reprocessing", reprocess::run(selection).await);
+        }
+        Command::Offline { dir } => {
+            exit_on_error("Offline ingestion", offline::ingest_directory(&dir).await);
+        }
+        Command::Export { output } => exit_on_error("Export", cli::export(output)),
+        Command::Status => exit_on_error("Status", cli::status()),
+    }
 }
@@ -0,0 +1,178 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::constants;
+use crate::error::FetchError;
+use crate::pubg_client::PubgClient;
+use crate::write_atomic;
+use shared_utils::paths::data_path;
+use shared_utils::redis_loader::load_player_stats_to_redis;
+
+#[derive(Debug, Deserialize)]
+struct MasteryResponse<T> {
+    data: MasteryData<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MasteryData<T> {
+    attributes: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct WeaponMasteryAttributes {
+    #[serde(rename = "weaponSummaries", default)]
+    weapon_summaries: HashMap<String, WeaponSummary>,
+    #[serde(rename = "latestMatchId", default)]
+    latest_match_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct WeaponSummary {
+    #[serde(rename = "XPTotal")]
+    pub xp_total: u32,
+    #[serde(rename = "LevelCurrent")]
+    pub level_current: u32,
+    #[serde(rename = "TierCurrent")]
+    pub tier_current: u32,
+    #[serde(rename = "StatsTotal")]
+    pub stats_total: WeaponStats,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct WeaponStats {
+    pub kills: u32,
+    pub defeats: u32,
+    pub damage_player: f64,
+    pub head_shots: u32,
+    pub groggies: u32,
+    pub long_range_defeats: u32,
+    pub longest_defeat: f64,
+    pub most_kills_in_a_game: u32,
+    pub most_damage_player_in_a_game: f64,
+}
+
+/// One weapon's official mastery, `name` is the name our telemetry stats use.
+#[derive(Serialize, Debug)]
+struct WeaponMastery {
+    item_id: String,
+    name: String,
+    #[serde(flatten)]
+    summary: WeaponSummary,
+}
+
+#[derive(Serialize, Debug)]
+struct PlayerWeaponMastery {
+    name: String,
+    account_id: String,
+    shard: String,
+    updated_at: String,
+    latest_match_id: Option<String>,
+    weapons: Vec<WeaponMastery>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SurvivalMasteryAttributes {
+    #[serde(default)]
+    xp: u32,
+    #[serde(default)]
+    tier: u32,
+    #[serde(default)]
+    level: u32,
+    #[serde(default)]
+    total_matches_played: u32,
+    #[serde(default)]
+    latest_match_id: Option<String>,
+    #[serde(default)]
+    stats: BTreeMap<String, SurvivalStat>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SurvivalStat {
+    pub total: f64,
+    pub average: f64,
+    pub career_best: f64,
+    pub last_match_value: f64,
+}
+
+#[derive(Serialize, Debug)]
+struct PlayerSurvivalMastery {
+    name: String,
+    account_id: String,
+    shard: String,
+    updated_at: String,
+    xp: u32,
+    tier: u32,
+    level: u32,
+    total_matches_played: u32,
+    latest_match_id: Option<String>,
+    stats: BTreeMap<String, SurvivalStat>,
+}
+
+async fn fetch_attributes<T: serde::de::DeserializeOwned>(pubg_client: &PubgClient, shard: &str, account_id: &str, endpoint: &str) -> Result<T, FetchError> {
+    let url = pubg_client.shard_url(shard, &format!("players/{}/{}", account_id, endpoint));
+    let response = pubg_client.get(&url).await?;
+    let parsed: MasteryResponse<T> = serde_json::from_str(&response).map_err(|e| FetchError::json(format!("{} of {}", endpoint, account_id), e))?;
+    Ok(parsed.data.attributes)
+}
+
+fn save(folder: &str, player_name: &str, data: &impl Serialize) -> Result<(), FetchError> {
+    let json_data = serde_json::to_string_pretty(data).map_err(|e| FetchError::json(folder, e))?;
+    write_atomic(&data_path(&format!("{}/{}.json", folder, player_name)), json_data.as_bytes())?;
+
+    if let Err(e) = load_player_stats_to_redis(folder, player_name) {
+        eprintln!("Failed to load {} of {} into Redis: {}", folder, player_name, e);
+    }
+    Ok(())
+}
+
+/// `/players/{id}/weapon_mastery` into `data/weapon_mastery/{name}.json`,
+/// most XP first.
+pub async fn fetch_weapon_mastery(pubg_client: &PubgClient, shard: &str, account_id: &str, player_name: &str) -> Result<(), FetchError> {
+    let attributes: WeaponMasteryAttributes = fetch_attributes(pubg_client, shard, account_id, "weapon_mastery").await?;
+
+    let mut weapons: Vec<WeaponMastery> = attributes
+        .weapon_summaries
+        .into_iter()
+        .map(|(item_id, summary)| {
+            let name = constants::weapons::WEAPONS
+                .iter()
+                .find(|&&(key, _)| key == item_id)
+                .map(|&(_, name)| name.to_string())
+                .unwrap_or_else(|| "Unknown".to_string());
+            WeaponMastery { item_id, name, summary }
+        })
+        .collect();
+    weapons.sort_by(|a, b| b.summary.xp_total.cmp(&a.summary.xp_total).then_with(|| a.item_id.cmp(&b.item_id)));
+
+    save("weapon_mastery", player_name, &PlayerWeaponMastery {
+        name: player_name.to_string(),
+        account_id: account_id.to_string(),
+        shard: shard.to_string(),
+        updated_at: Utc::now().to_rfc3339(),
+        latest_match_id: attributes.latest_match_id,
+        weapons,
+    })
+}
+
+/// `/players/{id}/survival_mastery` into `data/survival_mastery/{name}.json`.
+pub async fn fetch_survival_mastery(pubg_client: &PubgClient, shard: &str, account_id: &str, player_name: &str) -> Result<(), FetchError> {
+    let attributes: SurvivalMasteryAttributes = fetch_attributes(pubg_client, shard, account_id, "survival_mastery").await?;
+
+    save("survival_mastery", player_name, &PlayerSurvivalMastery {
+        name: player_name.to_string(),
+        account_id: account_id.to_string(),
+        shard: shard.to_string(),
+        updated_at: Utc::now().to_rfc3339(),
+        xp: attributes.xp,
+        tier: attributes.tier,
+        level: attributes.level,
+        total_matches_played: attributes.total_matches_played,
+        latest_match_id: attributes.latest_match_id,
+        stats: attributes.stats,
+    })
+}
@@ -0,0 +1,428 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+use crate::error::FetchError;
+use crate::identity::TrackedPlayers;
+use crate::telemetry_events::{Character, TelemetryEvent, Vehicle};
+use crate::write_atomic;
+use crate::{Event, Location, Target};
+use shared_utils::paths::data_path;
+use shared_utils::redis_loader::load_match_paths_to_redis;
+
+// pisteitä harvennetaan, sijainti tulee noin 10 s välein ja jokaisesta tapahtumasta
+const MIN_STEP_METERS: f32 = 20.0;
+// common.isGame, alle tämän ollaan vielä aulassa
+const IS_GAME_PLANE: f32 = 0.1;
+const AIRCRAFT: &str = "TransportAircraft";
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    Plane,
+    Parachute,
+    Foot,
+    Vehicle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Plane,
+    Parachute,
+    Ground,
+}
+
+impl Phase {
+    fn outside_vehicle(self) -> Mode {
+        match self {
+            Phase::Parachute => Mode::Parachute,
+            _ => Mode::Foot,
+        }
+    }
+}
+
+/// What the event tells about how the player is moving.
+enum Hint<'a> {
+    Position(Option<&'a Vehicle>),
+    Ride,
+    Leave(Option<&'a Vehicle>),
+    Landing,
+    Other,
+}
+
+struct Who<'a> {
+    account_id: &'a str,
+    name: &'a str,
+    team_id: i32,
+    location: &'a Location,
+    is_in_vehicle: bool,
+}
+
+impl<'a> From<&'a Character> for Who<'a> {
+    fn from(character: &'a Character) -> Self {
+        Who {
+            account_id: &character.account_id,
+            name: &character.name,
+            team_id: character.team_id,
+            location: &character.location,
+            is_in_vehicle: character.is_in_vehicle,
+        }
+    }
+}
+
+impl<'a> From<&'a Target> for Who<'a> {
+    fn from(target: &'a Target) -> Self {
+        Who {
+            account_id: &target.account_id,
+            name: &target.name,
+            team_id: target.team_id,
+            location: &target.location,
+            is_in_vehicle: target.is_in_vehicle,
+        }
+    }
+}
+
+struct Sample {
+    time: DateTime<FixedOffset>,
+    location: Location,
+    mode: Mode,
+}
+
+struct Track {
+    name: String,
+    team_id: i32,
+    phase: Phase,
+    samples: Vec<Sample>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PathPoint {
+    /// Seconds since `LogMatchDefinition`, like `_D` in `matches/{id}.json`.
+    pub time: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    /// How the player got here from the previous point.
+    pub mode: Mode,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlayerPath {
+    pub name: String,
+    pub account_id: String,
+    pub team_id: i32,
+    /// Meters, from every sample before downsampling.
+    pub walk_distance: f32,
+    pub drive_distance: f32,
+    pub path: Vec<PathPoint>,
+}
+
+/// `data/paths/{id}.json`, the paths of the squads with a tracked player.
+#[derive(Serialize, Debug)]
+pub struct MatchPaths {
+    pub match_id: String,
+    pub map_name: String,
+    pub players: Vec<PlayerPath>,
+}
+
+/// Collects every location the telemetry gives for the players in a squad
+/// with a tracked player, from `LogPlayerPosition` and from the characters
+/// of other events.
+pub struct Movement {
+    tracked: TrackedPlayers,
+    tracked_teams: HashSet<i32>,
+    tracks: HashMap<String, Track>,
+}
+
+impl Movement {
+    pub fn new(tracked: TrackedPlayers) -> Self {
+        Movement { tracked, tracked_teams: HashSet::new(), tracks: HashMap::new() }
+    }
+
+    pub fn visit(&mut self, event: &TelemetryEvent) {
+        match event {
+            // squadit selviävät jo ennen ensimmäistä sijaintia
+            TelemetryEvent::LogMatchStart(e) => {
+                for character in e.characters.iter().filter_map(|wrapper| wrapper.character.as_ref()) {
+                    if self.tracked.contains(&character.account_id, &character.name) {
+                        self.tracked_teams.insert(character.team_id);
+                    }
+                }
+            }
+            TelemetryEvent::LogPlayerPosition(e) => self.observe_character(&e.date, e.common.is_game, &e.character, Hint::Position(e.vehicle.as_ref())),
+            TelemetryEvent::LogParachuteLanding(e) => self.observe_character(&e.date, e.common.is_game, &e.character, Hint::Landing),
+            TelemetryEvent::LogVehicleRide(e) => self.observe_character(&e.date, e.common.is_game, &e.character, Hint::Ride),
+            TelemetryEvent::LogVehicleLeave(e) => self.observe_character(&e.date, e.common.is_game, &e.character, Hint::Leave(e.vehicle.as_ref())),
+            TelemetryEvent::LogHeal(e) => self.observe_character(&e.date, e.common.is_game, &e.character, Hint::Other),
+            TelemetryEvent::LogItemPickup(e) | TelemetryEvent::LogItemUse(e) | TelemetryEvent::LogItemDrop(e) | TelemetryEvent::LogItemUnequip(e) => {
+                self.observe_character(&e.date, e.common.is_game, &e.character, Hint::Other)
+            }
+            TelemetryEvent::LogItemAttach(e) | TelemetryEvent::LogItemDetach(e) => self.observe_character(&e.date, e.common.is_game, &e.character, Hint::Other),
+            TelemetryEvent::LogPlayerRevive(e) => {
+                self.observe_character(&e.date, e.common.is_game, &e.reviver, Hint::Other);
+                self.observe_character(&e.date, e.common.is_game, &e.victim, Hint::Other);
+            }
+            TelemetryEvent::LogPlayerUseThrowable(e) => self.observe_character(&e.date, e.common.is_game, &e.attacker, Hint::Other),
+            TelemetryEvent::LogWeaponFireCount(e) => self.observe_character(&e.date, e.common.is_game, &e.character, Hint::Other),
+            TelemetryEvent::LogObjectDestroy(e) => self.observe_character(&e.date, e.common.is_game, &e.character, Hint::Other),
+            TelemetryEvent::LogVaultStart(e) | TelemetryEvent::LogSwimStart(e) | TelemetryEvent::LogSwimEnd(e) => {
+                self.observe_character(&e.date, e.common.is_game, &e.character, Hint::Other)
+            }
+            TelemetryEvent::LogPlayerTakeDamage(e)
+            | TelemetryEvent::LogPlayerAttack(e)
+            | TelemetryEvent::LogPlayerMakeGroggy(e)
+            | TelemetryEvent::LogArmorDestroy(e)
+            | TelemetryEvent::LogPlayerKillV2(e)
+            | TelemetryEvent::LogItemEquip(e)
+            | TelemetryEvent::LogItemPickupFromCarepackage(e)
+            | TelemetryEvent::LogItemPickupFromLootbox(e) => self.observe_event(e),
+            _ => {}
+        }
+    }
+
+    // PUBG lähettää hahmon paikalla joskus null
+    fn observe_character(&mut self, date: &str, is_game: f32, character: &Option<Character>, hint: Hint) {
+        if let Some(character) = character {
+            self.observe(date, is_game, character.into(), hint);
+        }
+    }
+
+    fn observe_event(&mut self, event: &Event) {
+        let Some(date) = &event.event_time else {
+            return;
+        };
+        let is_game = event
+            .extra
+            .get("common")
+            .and_then(|common| common.get("isGame"))
+            .and_then(Value::as_f64)
+            .unwrap_or(1.0) as f32;
+        let targets = [&event.attacker, &event.victim, &event.dbno_maker, &event.finisher, &event.killer, &event.player];
+        for target in targets.into_iter().flatten() {
+            self.observe(date, is_game, target.into(), Hint::Other);
+        }
+    }
+
+    fn observe(&mut self, date: &str, is_game: f32, who: Who, hint: Hint) {
+        if is_game < IS_GAME_PLANE || who.account_id.is_empty() {
+            return;
+        }
+        if self.tracked.contains(who.account_id, who.name) {
+            self.tracked_teams.insert(who.team_id);
+        }
+        if !self.tracked_teams.contains(&who.team_id) {
+            return;
+        }
+        let Ok(time) = DateTime::parse_from_rfc3339(date) else {
+            return;
+        };
+
+        let track = self.tracks.entry(who.account_id.to_string()).or_insert_with(|| Track {
+            name: who.name.to_string(),
+            team_id: who.team_id,
+            phase: Phase::Ground,
+            samples: Vec::new(),
+        });
+        // piste kertoo miten siihen tultiin edellisestä pisteestä
+        let mode = match hint {
+            Hint::Position(Some(vehicle)) if vehicle.vehicle_type == AIRCRAFT => {
+                track.phase = Phase::Plane;
+                Mode::Plane
+            }
+            Hint::Position(Some(_)) => Mode::Vehicle,
+            Hint::Position(None) | Hint::Ride => track.phase.outside_vehicle(),
+            Hint::Leave(Some(vehicle)) if vehicle.vehicle_type == AIRCRAFT => {
+                track.phase = Phase::Parachute;
+                Mode::Plane
+            }
+            Hint::Leave(_) => Mode::Vehicle,
+            Hint::Landing => {
+                track.phase = Phase::Ground;
+                Mode::Parachute
+            }
+            Hint::Other if who.is_in_vehicle && track.phase == Phase::Plane => Mode::Plane,
+            Hint::Other if who.is_in_vehicle => Mode::Vehicle,
+            Hint::Other => track.phase.outside_vehicle(),
+        };
+        track.samples.push(Sample { time, location: who.location.clone(), mode });
+    }
+
+    /// Time ordered, downsampled paths of every player in a squad with a
+    /// tracked player. Tracked players get their current name.
+    pub fn into_paths(self, match_id: &str, map_name: &str, match_start_time: &str) -> MatchPaths {
+        let start = DateTime::parse_from_rfc3339(match_start_time).ok();
+        let Movement { tracked, tracked_teams, tracks } = self;
+
+        let mut players: Vec<PlayerPath> = tracks
+            .into_iter()
+            .filter(|(_, track)| tracked_teams.contains(&track.team_id))
+            .map(|(account_id, mut track)| {
+                track.samples.sort_by_key(|sample| sample.time);
+                let start = start.or_else(|| track.samples.first().map(|sample| sample.time));
+                let (walk_distance, drive_distance) = distances(&track.samples);
+                let name = tracked.current_name(&account_id).map(str::to_string).unwrap_or(track.name);
+                PlayerPath {
+                    name,
+                    account_id,
+                    team_id: track.team_id,
+                    walk_distance,
+                    drive_distance,
+                    path: downsample(&track.samples, start),
+                }
+            })
+            .collect();
+        players.sort_by(|a, b| a.team_id.cmp(&b.team_id).then_with(|| a.name.cmp(&b.name)));
+
+        MatchPaths { match_id: match_id.to_string(), map_name: map_name.to_string(), players }
+    }
+}
+
+fn distances(samples: &[Sample]) -> (f32, f32) {
+    let mut walk = 0.0;
+    let mut drive = 0.0;
+    for pair in samples.windows(2) {
+        let step = pair[0].location.distance(&pair[1].location);
+        match pair[1].mode {
+            Mode::Foot => walk += step,
+            Mode::Vehicle => drive += step,
+            Mode::Plane | Mode::Parachute => {}
+        }
+    }
+    (walk, drive)
+}
+
+/// Keeps the first and last sample, every change of mode and otherwise only
+/// samples at least `MIN_STEP_METERS` from the previous kept one.
+fn downsample(samples: &[Sample], start: Option<DateTime<FixedOffset>>) -> Vec<PathPoint> {
+    let mut kept: Vec<&Sample> = Vec::new();
+    for (index, sample) in samples.iter().enumerate() {
+        let keep = match kept.last() {
+            None => true,
+            Some(previous) => {
+                previous.mode != sample.mode || previous.location.distance(&sample.location) >= MIN_STEP_METERS || index == samples.len() - 1
+            }
+        };
+        if keep {
+            kept.push(sample);
+        }
+    }
+
+    kept.into_iter()
+        .map(|sample| PathPoint {
+            time: start.map(|start| (sample.time - start).num_milliseconds() as f32 / 1000.0).unwrap_or(0.0),
+            x: sample.location.x,
+            y: sample.location.y,
+            z: sample.location.z,
+            mode: sample.mode,
+        })
+        .collect()
+}
+
+/// Writes `data/paths/{id}.json` and loads it into Redis.
+pub fn save(paths: &MatchPaths) -> Result<(), FetchError> {
+    let json_data = serde_json::to_string_pretty(paths).map_err(|e| FetchError::json(format!("paths of match {}", paths.match_id), e))?;
+    write_atomic(&data_path(&format!("paths/{}.json", paths.match_id)), json_data.as_bytes())?;
+
+    if let Err(e) = load_match_paths_to_redis(&paths.match_id) {
+        eprintln!("Failed to load paths of match {} into Redis: {}", paths.match_id, e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry_events::parse;
+    use chrono::Duration;
+    use serde_json::json;
+
+    const START: &str = "2025-05-01T18:00:00Z";
+
+    fn at(seconds: i64) -> String {
+        (DateTime::parse_from_rfc3339(START).unwrap() + Duration::seconds(seconds)).to_rfc3339()
+    }
+
+    fn character(account_id: &str, team_id: i32, x: f32) -> Value {
+        json!({"name": account_id, "accountId": account_id, "teamId": team_id, "location": {"x": x, "y": 0.0, "z": 0.0}})
+    }
+
+    fn event(kind: &str, seconds: i64, fields: Value) -> TelemetryEvent {
+        let mut value = json!({"_T": kind, "_D": at(seconds), "common": {"isGame": 1.0}});
+        value.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+        parse(value)
+    }
+
+    fn position(seconds: i64, account_id: &str, team_id: i32, x: f32, vehicle: Option<&str>) -> TelemetryEvent {
+        let vehicle = vehicle.map(|vehicle_type| json!({"vehicleType": vehicle_type}));
+        event("LogPlayerPosition", seconds, json!({"character": character(account_id, team_id, x), "vehicle": vehicle}))
+    }
+
+    fn paths(tracked: &[&str], events: Vec<TelemetryEvent>) -> MatchPaths {
+        let mut movement = Movement::new(TrackedPlayers::of(tracked));
+        for event in &events {
+            movement.visit(event);
+        }
+        movement.into_paths("match", "Baltic_Main", START)
+    }
+
+    #[test]
+    fn classifies_plane_parachute_foot_and_vehicle() {
+        let paths = paths(
+            &["a"],
+            vec![
+                position(10, "a", 1, 0.0, Some(AIRCRAFT)),
+                event("LogVehicleLeave", 20, json!({"character": character("a", 1, 5000.0), "vehicle": {"vehicleType": AIRCRAFT}})),
+                position(30, "a", 1, 10000.0, None),
+                event("LogParachuteLanding", 40, json!({"character": character("a", 1, 15000.0)})),
+                position(50, "a", 1, 20000.0, None),
+                position(60, "a", 1, 25000.0, Some("WheeledVehicle")),
+            ],
+        );
+        let modes: Vec<Mode> = paths.players[0].path.iter().map(|point| point.mode).collect();
+        assert_eq!(modes, [Mode::Plane, Mode::Plane, Mode::Parachute, Mode::Parachute, Mode::Foot, Mode::Vehicle]);
+        let times: Vec<f32> = paths.players[0].path.iter().map(|point| point.time).collect();
+        assert_eq!(times, [10.0, 20.0, 30.0, 40.0, 50.0, 60.0]);
+    }
+
+    #[test]
+    fn splits_distance_into_walking_and_driving() {
+        let paths = paths(
+            &["a"],
+            vec![
+                position(10, "a", 1, 0.0, None),
+                position(20, "a", 1, 10000.0, None),
+                position(30, "a", 1, 40000.0, Some("WheeledVehicle")),
+                position(40, "a", 1, 45000.0, None),
+            ],
+        );
+        let player = &paths.players[0];
+        assert_eq!(player.walk_distance, 150.0);
+        assert_eq!(player.drive_distance, 300.0);
+    }
+
+    #[test]
+    fn downsamples_short_steps() {
+        // 5 m välein, 20 m:n harvennus jättää joka neljännen ja viimeisen
+        let events = (0..=10).map(|step| position(step * 10, "a", 1, step as f32 * 500.0, None)).collect();
+        let paths = paths(&["a"], events);
+        let xs: Vec<f32> = paths.players[0].path.iter().map(|point| point.x).collect();
+        assert_eq!(xs, [0.0, 2000.0, 4000.0, 5000.0]);
+        assert_eq!(paths.players[0].walk_distance, 50.0);
+    }
+
+    #[test]
+    fn keeps_only_tracked_squads() {
+        let paths = paths(
+            &["a"],
+            vec![
+                event("LogMatchStart", 0, json!({"characters": [{"character": character("a", 1, 0.0)}]})),
+                position(10, "b", 1, 0.0, None),
+                position(10, "c", 2, 0.0, None),
+                position(20, "a", 1, 0.0, None),
+            ],
+        );
+        let players: Vec<&str> = paths.players.iter().map(|player| player.account_id.as_str()).collect();
+        assert_eq!(players, ["a", "b"]);
+    }
+}
@@ -0,0 +1,203 @@
+use flate2::read::GzDecoder;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::error::FetchError;
+use crate::ingest_status::IngestStatus;
+use crate::roster;
+use crate::{archive_match_response, fetch_match_data, parse_match_response, MatchResponse};
+
+/// Where `fetch_telemetry_data` gets the telemetry for a match from.
+#[derive(Clone)]
+pub enum TelemetrySource {
+    /// Download from the asset URL in the match response.
+    Api,
+    /// Read previously downloaded files from a directory, see [`open_local_telemetry`].
+    Directory(PathBuf),
+    /// Read the copy in `data/archive/`, rebuilding an already stored match.
+    Archive,
+}
+
+/// Reads a JSON file, transparently decompressing it if it is gzipped.
+pub fn read_json_file(path: &Path) -> Result<String, FetchError> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|e| FetchError::storage(format!("reading {}", path.display()), e))?;
+
+    // gzip magic number, toimii vaikka tiedostopääte puuttuisi
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut contents = String::new();
+        GzDecoder::new(bytes.as_slice())
+            .read_to_string(&mut contents)
+            .map_err(|e| FetchError::storage(format!("decompressing {}", path.display()), e))?;
+        return Ok(contents);
+    }
+    String::from_utf8(bytes).map_err(|e| FetchError::storage(format!("reading {}", path.display()), e))
+}
+
+/// Opens the telemetry for `match_id` in `dir` for streaming. Accepts `{match_id}.telemetry.json`
+/// or the file name of the CDN asset URL, both optionally with a `.gz` suffix.
+pub fn open_local_telemetry(dir: &Path, match_id: &str, telemetry_url: &str) -> Result<Box<dyn Read>, FetchError> {
+    let mut candidates = vec![format!("{}.telemetry.json", match_id)];
+    if let Some(asset_name) = telemetry_url.rsplit('/').next().filter(|name| !name.is_empty()) {
+        candidates.push(asset_name.to_string());
+    }
+
+    for candidate in &candidates {
+        for name in [candidate.clone(), format!("{}.gz", candidate)] {
+            let path = dir.join(&name);
+            if path.exists() {
+                let file = File::open(&path).map_err(|e| FetchError::storage(format!("reading {}", path.display()), e))?;
+                let mut reader = BufReader::new(file);
+                let gzipped = reader
+                    .fill_buf()
+                    .map_err(|e| FetchError::storage(format!("reading {}", path.display()), e))?
+                    .starts_with(&[0x1f, 0x8b]);
+                if gzipped {
+                    return Ok(Box::new(BufReader::new(GzDecoder::new(reader))));
+                }
+                return Ok(Box::new(reader));
+            }
+        }
+    }
+    Err(FetchError::MissingTelemetry {
+        match_id: match_id.to_string(),
+        detail: format!("no file in {} (tried {:?})", dir.display(), candidates),
+    })
+}
+
+fn is_match_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    (name.ends_with(".json") || name.ends_with(".json.gz")) && !name.contains("telemetry")
+}
+
+/// Runs every match response in `dir` through the normal enrichment pipeline,
+/// oldest match first, with telemetry read from the same directory.
+pub async fn ingest_directory(dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut status = IngestStatus::load()?;
+    let mut matches: Vec<(String, MatchResponse)> = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !is_match_file(&path) {
+            continue;
+        }
+
+        let response = read_json_file(&path)?;
+        match parse_match_response(&response) {
+            Ok(parsed) => {
+                let shard = parsed.data.attributes.shard_id.clone().unwrap_or_else(|| roster::DEFAULT_SHARD.to_string());
+                archive_match_response(&parsed, &shard, &response);
+                matches.push((shard, parsed));
+            }
+            Err(e) => eprintln!("Skipping {}: not a match response ({})", path.display(), e),
+        }
+    }
+    matches.sort_by(|a, b| a.1.data.attributes.created_at.cmp(&b.1.data.attributes.created_at));
+    println!("Found {} matches in {}", matches.len(), dir.display());
+
+    let source = TelemetrySource::Directory(dir.to_path_buf());
+    let total = matches.len();
+    let mut failed = 0;
+    for (shard, response) in matches {
+        let match_id = response.data.id.clone();
+        match fetch_match_data(response, &shard, &source).await {
+            Ok(()) => status.mark_done(&match_id),
+            Err(e) => {
+                eprintln!("Failed to ingest match {}: {}", match_id, e);
+                status.mark_failed(&match_id, e.to_string());
+                failed += 1;
+            }
+        }
+    }
+    status.save()?;
+
+    println!("Ingested {} of {} matches", total - failed, total);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use shared_utils::paths::{data_dir, set_data_dir};
+    use shared_utils::redis_loader::set_redis_url;
+
+    const FIXTURE_MATCH: &str = "6f1c2a9e-3b4d-4e5f-8a7b-9c0d1e2f3a4b";
+
+    fn read(relative: &str) -> Value {
+        let contents = fs::read_to_string(data_dir().join(relative)).unwrap();
+        serde_json::from_str(&contents).unwrap()
+    }
+
+    #[tokio::test]
+    async fn ingests_the_mock_api_fixtures() {
+        let dir = std::env::temp_dir().join(format!("api-fetch-offline-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        set_data_dir(&dir);
+        // redis ei ole käynnissä, lataukset vain epäonnistuvat
+        set_redis_url("redis://127.0.0.1:1/");
+        roster::init().unwrap();
+
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("../fixtures/matches");
+        ingest_directory(&fixtures).await.unwrap();
+
+        let summaries = read("matches.json");
+        let summaries = summaries.as_array().unwrap();
+        assert_eq!(summaries.len(), 1);
+        let summary = &summaries[0];
+        assert_eq!(summary["id"], FIXTURE_MATCH);
+        assert_eq!(summary["map_name"], "Erangel");
+        assert_eq!(summary["game_mode"], "squad-fpp");
+        let squad: Vec<&str> = summary["squad"].as_array().unwrap().iter().map(|player| player["name"].as_str().unwrap()).collect();
+        assert!(squad.contains(&"E1_Duderino") && squad.contains(&"keken_viikset"));
+
+        let events = read(&format!("matches/{}.json", FIXTURE_MATCH));
+        let kinds: Vec<&str> = events.as_array().unwrap().iter().map(|event| event["_T"].as_str().unwrap()).collect();
+        assert_eq!(kinds.iter().filter(|kind| **kind == "LogPlayerTakeDamage").count(), 4);
+        assert!(kinds.contains(&"LogArmorDestroy"));
+
+        let paths = read(&format!("paths/{}.json", FIXTURE_MATCH));
+        let players = paths["players"].as_array().unwrap();
+        assert_eq!(players.len(), 4);
+        for player in players {
+            let modes: Vec<&str> = player["path"].as_array().unwrap().iter().map(|point| point["mode"].as_str().unwrap()).collect();
+            assert_eq!(modes[..3], ["plane", "parachute", "foot"]);
+            assert!(player["walk_distance"].as_f64().unwrap() > 0.0);
+        }
+        let driver = players.iter().find(|player| player["name"] == "bold_moves_bob").unwrap();
+        assert!(driver["drive_distance"].as_f64().unwrap() > 0.0);
+        assert_eq!(paths["map_name"], summary["map_name"]);
+
+        let zones = read(&format!("zones/{}.json", FIXTURE_MATCH));
+        assert_eq!(zones["map_name"], summary["map_name"]);
+        assert_eq!(zones["timeline"].as_array().unwrap().len(), 10);
+        let phases = zones["phases"].as_array().unwrap();
+        assert_eq!(phases.len(), 10);
+        for pair in phases.windows(2) {
+            assert_eq!(pair[0]["end"], pair[1]["start"]);
+        }
+        let zone_players = zones["players"].as_array().unwrap();
+        assert_eq!(zone_players.len(), 4);
+        for player in zone_players {
+            assert!(!player["phases"].as_array().unwrap().is_empty());
+        }
+        let burned = zone_players.iter().find(|player| player["name"] == "keken_viikset").unwrap();
+        assert_eq!(burned["blue_zone_damage"], 10.0);
+
+        let landings = read("landings.json");
+        let landing = &landings.as_array().unwrap()[0];
+        assert_eq!(landing["match_id"], FIXTURE_MATCH);
+        assert_eq!(landing["map_name"], summary["map_name"]);
+
+        let status = read("ingest_status.json");
+        assert_eq!(status[FIXTURE_MATCH]["status"], "done");
+        assert!(dir.join("archive").join(FIXTURE_MATCH).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
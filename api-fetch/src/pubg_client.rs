@@ -0,0 +1,222 @@
+use reqwest::{header, Client, Response, StatusCode};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+use crate::error::FetchError;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.pubg.com";
+const MAX_RETRIES: u32 = 5;
+// jos 429 tulee ilman X-RateLimit-Reset headeria
+const FALLBACK_BACKOFF_SECS: u64 = 6;
+const MAX_BACKOFF_SECS: u64 = 120;
+
+#[derive(Debug, Default)]
+struct RateLimitState {
+    limit: Option<u32>,
+    remaining: Option<u32>,
+    reset_at: Option<u64>,
+}
+
+/// Shared client for the rate limited `api.pubg.com` endpoints.
+///
+/// Requests are queued behind a fair mutex, so only one request is in flight
+/// at a time and the `X-RateLimit-*` headers of the previous response decide
+/// whether the next one has to wait for the window to reset.
+pub struct PubgClient {
+    client: Client,
+    base_url: String,
+    headers: header::HeaderMap,
+    state: Mutex<RateLimitState>,
+}
+
+fn make_headers(api_key: &str) -> Result<header::HeaderMap, Box<dyn std::error::Error>> {
+    let mut headers = header::HeaderMap::new();
+    headers.insert(header::ACCEPT, "application/vnd.api+json".parse()?);
+    headers.insert(header::AUTHORIZATION, format!("Bearer {}", api_key).parse()?);
+    Ok(headers)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn header_number(response: &Response, name: &str) -> Option<u64> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+}
+
+impl RateLimitState {
+    fn update(&mut self, response: &Response) {
+        if let Some(limit) = header_number(response, "X-RateLimit-Limit") {
+            self.limit = Some(limit as u32);
+        }
+        if let Some(remaining) = header_number(response, "X-RateLimit-Remaining") {
+            self.remaining = Some(remaining as u32);
+        }
+        if let Some(reset) = header_number(response, "X-RateLimit-Reset") {
+            self.reset_at = Some(reset);
+        }
+    }
+
+    fn wait_time(&self) -> Option<Duration> {
+        match (self.remaining, self.reset_at) {
+            (Some(0), Some(reset_at)) => {
+                let now = now_secs();
+                // +1 s ettei osuta just ennen kuin ikkuna oikeasti vaihtuu
+                (reset_at >= now).then(|| Duration::from_secs(reset_at - now + 1))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl PubgClient {
+    pub fn new(api_key: &str, base_url: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(PubgClient {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            headers: make_headers(api_key)?,
+            state: Mutex::new(RateLimitState::default()),
+        })
+    }
+
+    /// `{base_url}/shards/{shard}/{path}`
+    pub fn shard_url(&self, shard: &str, path: &str) -> String {
+        format!("{}/shards/{}/{}", self.base_url, shard, path)
+    }
+
+    /// GETs `url` and returns the body, waiting out the rate limit window
+    /// and retrying on 429 responses after `Retry-After` seconds, or after
+    /// the window resets if the response has no `Retry-After`.
+    pub async fn get(&self, url: &str) -> Result<String, FetchError> {
+        let mut state = self.state.lock().await;
+        let mut attempt = 0;
+
+        loop {
+            if let Some(wait) = state.wait_time() {
+                println!("Rate limit reached ({:?} requests/min), waiting {}s", state.limit, wait.as_secs());
+                sleep(wait).await;
+                state.remaining = None;
+            }
+
+            let response = self
+                .client
+                .get(url)
+                .headers(self.headers.clone())
+                .send()
+                .await?;
+            state.update(&response);
+
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                attempt += 1;
+                if attempt > MAX_RETRIES {
+                    return Err(FetchError::RateLimited { url: url.to_string(), retries: MAX_RETRIES });
+                }
+
+                // Retry-After sekunteina voittaa, sitten ikkunan reset, viimeisenä oma backoff
+                let backoff = header_number(&response, "Retry-After")
+                    .map(|secs| Duration::from_secs(secs.min(MAX_BACKOFF_SECS)))
+                    .or_else(|| state.wait_time())
+                    .unwrap_or_else(|| Duration::from_secs((FALLBACK_BACKOFF_SECS << (attempt - 1)).min(MAX_BACKOFF_SECS)));
+                eprintln!("429 from {}, retrying in {}s (attempt {}/{})", url, backoff.as_secs(), attempt, MAX_RETRIES);
+                sleep(backoff).await;
+                state.remaining = None;
+                continue;
+            }
+
+            let response = response.error_for_status()?;
+            return Ok(response.text().await?);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Instant;
+    use warp::http::StatusCode as WarpStatus;
+    use warp::Filter;
+
+    /// Serves `/` on a free port, `reply` gets the number of the request
+    /// starting from 1. Returns the url and the request counter.
+    fn stub<F>(reply: F) -> (String, Arc<AtomicU32>)
+    where
+        F: Fn(u32) -> warp::reply::Response + Clone + Send + Sync + 'static,
+    {
+        let hits = Arc::new(AtomicU32::new(0));
+        let counter = hits.clone();
+        let route = warp::path::end().map(move || reply(counter.fetch_add(1, Ordering::SeqCst) + 1));
+        let (address, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{}/", address), hits)
+    }
+
+    fn response(status: WarpStatus, headers: &[(&'static str, String)]) -> warp::reply::Response {
+        let mut response = warp::http::Response::new("ok".into());
+        *response.status_mut() = status;
+        for (name, value) in headers {
+            response.headers_mut().insert(*name, value.parse().unwrap());
+        }
+        response
+    }
+
+    #[tokio::test]
+    async fn retries_after_429_with_retry_after() {
+        let (url, hits) = stub(|hit| match hit {
+            1 => response(WarpStatus::TOO_MANY_REQUESTS, &[("Retry-After", "1".to_string())]),
+            _ => response(WarpStatus::OK, &[]),
+        });
+        let client = PubgClient::new("test", &url).unwrap();
+
+        let started = Instant::now();
+        let body = client.get(&url).await.unwrap();
+
+        assert_eq!(body, "ok");
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        // Retry-After voittaa oman 6 s fallbackin
+        assert!(started.elapsed() < Duration::from_secs(FALLBACK_BACKOFF_SECS));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let (url, hits) = stub(|_| response(WarpStatus::TOO_MANY_REQUESTS, &[("Retry-After", "0".to_string())]));
+        let client = PubgClient::new("test", &url).unwrap();
+
+        let result = client.get(&url).await;
+
+        assert!(matches!(result, Err(FetchError::RateLimited { retries: MAX_RETRIES, .. })));
+        assert_eq!(hits.load(Ordering::SeqCst), MAX_RETRIES + 1);
+    }
+
+    #[tokio::test]
+    async fn waits_for_reset_when_no_requests_remain() {
+        let reset = now_secs() + 1;
+        let (url, hits) = stub(move |hit| match hit {
+            1 => response(
+                WarpStatus::OK,
+                &[("X-RateLimit-Limit", "10".to_string()), ("X-RateLimit-Remaining", "0".to_string()), ("X-RateLimit-Reset", reset.to_string())],
+            ),
+            _ => response(WarpStatus::OK, &[]),
+        });
+        let client = PubgClient::new("test", &url).unwrap();
+
+        client.get(&url).await.unwrap();
+        let started = Instant::now();
+        client.get(&url).await.unwrap();
+
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        // odotetaan resetiin asti, +1 s varmuuden vuoksi
+        assert!(now_secs() >= reset);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,237 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use crate::error::FetchError;
+use crate::identity::TrackedPlayers;
+use crate::pubg_client::PubgClient;
+use crate::write_atomic;
+use crate::seasons::{self, Season};
+use crate::roster;
+use shared_utils::paths::data_path;
+use shared_utils::redis_loader::load_player_ranked_to_redis;
+
+/// `matchType` of ranked matches in `/matches/{id}`.
+pub const RANKED_MATCH_TYPE: &str = "competitive";
+
+#[derive(Debug, Deserialize)]
+struct RankedResponse {
+    data: RankedData,
+}
+
+#[derive(Debug, Deserialize)]
+struct RankedData {
+    attributes: RankedAttributes,
+}
+
+#[derive(Debug, Deserialize)]
+struct RankedAttributes {
+    #[serde(rename = "rankedGameModeStats", default)]
+    ranked_game_mode_stats: HashMap<String, RankedGameModeStats>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Tier {
+    pub tier: String,
+    #[serde(rename = "subTier")]
+    pub sub_tier: String,
+}
+
+/// Ranked counterpart of the season `GameModeStats`, the fields the API
+/// leaves out of some modes default to zero.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RankedGameModeStats {
+    pub current_tier: Tier,
+    pub current_rank_point: i32,
+    pub best_tier: Tier,
+    pub best_rank_point: i32,
+    pub rounds_played: u32,
+    pub avg_rank: f64,
+    pub top10_ratio: f64,
+    pub win_ratio: f64,
+    pub assists: u32,
+    pub wins: u32,
+    pub kda: f64,
+    pub kills: u32,
+    pub deaths: u32,
+    #[serde(rename = "dBNOs")]
+    pub dbnos: u32,
+    pub damage_dealt: f64,
+    pub headshot_kills: u32,
+    pub revives: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct PlayerRankedStats {
+    name: String,
+    account_id: String,
+    shard: String,
+    season_id: String,
+    updated_at: String,
+    game_modes: BTreeMap<String, RankedGameModeStats>,
+}
+
+/// One change of a player's RP, written when the RP differs from the
+/// previous entry of the same season and mode.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct RankPointChange {
+    at: String,
+    season_id: String,
+    game_mode: String,
+    /// Newest ranked match of the player before the change, when known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    match_id: Option<String>,
+    rank_point: i32,
+    change: i32,
+    tier: Tier,
+}
+
+/// A tracked player seen in a stored ranked match.
+#[derive(Debug, Clone)]
+pub struct RankedMatchPlayer {
+    pub name: String,
+    pub account_id: String,
+    pub shard: String,
+    pub match_id: String,
+}
+
+/// Tracked players of a match summary, if the match was ranked.
+pub fn tracked_players_in(summary: &HashMap<String, Value>) -> Vec<RankedMatchPlayer> {
+    if summary.get("match_type").and_then(Value::as_str) != Some(RANKED_MATCH_TYPE) {
+        return Vec::new();
+    }
+    let tracked = TrackedPlayers::load();
+    let shard = summary.get("shard").and_then(Value::as_str).unwrap_or(roster::DEFAULT_SHARD);
+    let match_id = summary.get("id").and_then(Value::as_str).unwrap_or_default();
+
+    summary
+        .get("squad")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|player| {
+            let name = player.get("name")?.as_str()?;
+            let account_id = player.get("playerId")?.as_str()?;
+            if !tracked.contains(account_id, name) {
+                return None;
+            }
+            Some(RankedMatchPlayer {
+                name: name.to_string(),
+                account_id: account_id.to_string(),
+                shard: shard.to_string(),
+                match_id: match_id.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn load_history(player_name: &str) -> Result<Vec<RankPointChange>, FetchError> {
+    match fs::read_to_string(data_path(&format!("ranked_history/{}.json", player_name))) {
+        Ok(contents) => serde_json::from_str(&contents).map_err(|e| FetchError::json(format!("ranked history of {}", player_name), e)),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+fn record_changes(stats: &PlayerRankedStats, match_id: Option<&str>) -> Result<(), FetchError> {
+    let mut history = load_history(&stats.name)?;
+    let mut changed = false;
+
+    for (game_mode, mode_stats) in &stats.game_modes {
+        let previous = history
+            .iter()
+            .rev()
+            .find(|change| change.season_id == stats.season_id && change.game_mode == *game_mode)
+            .map(|change| change.rank_point);
+        if previous == Some(mode_stats.current_rank_point) {
+            continue;
+        }
+
+        // kauden ensimmäinen rivi on lähtötaso, ei muutos
+        let change = previous.map(|previous| mode_stats.current_rank_point - previous).unwrap_or(0);
+        if previous.is_some() {
+            println!("{} {} RP {:+} -> {}", stats.name, game_mode, change, mode_stats.current_rank_point);
+        }
+        history.push(RankPointChange {
+            at: stats.updated_at.clone(),
+            season_id: stats.season_id.clone(),
+            game_mode: game_mode.clone(),
+            match_id: previous.and(match_id.map(str::to_string)),
+            rank_point: mode_stats.current_rank_point,
+            change,
+            tier: mode_stats.current_tier.clone(),
+        });
+        changed = true;
+    }
+
+    if changed {
+        let json_data = serde_json::to_string_pretty(&history).map_err(|e| FetchError::json("ranked history", e))?;
+        write_atomic(&data_path(&format!("ranked_history/{}.json", stats.name)), json_data.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Fetches a player's ranked stats for `season`, stores them in
+/// `data/ranked_stats/{name}.json` and appends RP changes to
+/// `data/ranked_history/{name}.json`. `match_id` is the ranked match that
+/// caused the refresh, if any. Both files follow the current season, so a
+/// past season is not fetched at all.
+pub async fn refresh(pubg_client: &PubgClient, shard: &str, season: &Season, account_id: &str, player_name: &str, match_id: Option<&str>) -> Result<(), FetchError> {
+    if !season.is_current_season {
+        return Ok(());
+    }
+    let season_id = season.id.as_str();
+    let url = pubg_client.shard_url(shard, &format!("players/{}/seasons/{}/ranked", account_id, season_id));
+    let response = match pubg_client.get(&url).await {
+        Ok(response) => response,
+        // ei rankattuja pelejä tällä kaudella
+        Err(FetchError::Status { status: 404, .. }) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let parsed: RankedResponse = serde_json::from_str(&response).map_err(|e| FetchError::json(format!("ranked stats of {}", player_name), e))?;
+
+    let stats = PlayerRankedStats {
+        name: player_name.to_string(),
+        account_id: account_id.to_string(),
+        shard: shard.to_string(),
+        season_id: season_id.to_string(),
+        updated_at: Utc::now().to_rfc3339(),
+        game_modes: parsed
+            .data
+            .attributes
+            .ranked_game_mode_stats
+            .into_iter()
+            .filter(|(_, mode_stats)| mode_stats.rounds_played > 0)
+            .collect(),
+    };
+
+    let json_data = serde_json::to_string_pretty(&stats).map_err(|e| FetchError::json("ranked stats", e))?;
+    write_atomic(&data_path(&format!("ranked_stats/{}.json", player_name)), json_data.as_bytes())?;
+    record_changes(&stats, match_id)?;
+
+    if let Err(e) = load_player_ranked_to_redis(player_name) {
+        eprintln!("Failed to load ranked stats of {} into Redis: {}", player_name, e);
+    }
+    Ok(())
+}
+
+/// Refreshes the ranked stats of everyone who played ranked in the newly
+/// stored matches, once per player with their newest ranked match.
+pub async fn refresh_after_matches(pubg_client: &PubgClient, players: Vec<RankedMatchPlayer>) {
+    let mut newest: HashMap<String, RankedMatchPlayer> = HashMap::new();
+    for player in players {
+        newest.insert(player.account_id.clone(), player);
+    }
+
+    for player in newest.into_values() {
+        let refreshed = match seasons::resolve(pubg_client, &player.shard, None).await {
+            Ok(season) => refresh(pubg_client, &player.shard, &season, &player.account_id, &player.name, Some(&player.match_id)).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = refreshed {
+            eprintln!("Failed to refresh ranked stats of {}: {}", player.name, e);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::archive::{self, ArchiveIndex};
+use crate::error::FetchError;
+use crate::ingest_status::{stored_match_ids, IngestStatus};
+use crate::offline::TelemetrySource;
+use crate::{fetch_match_data, parse_match_response, MatchResponse};
+
+pub enum Selection {
+    Match(String),
+    /// Every archived match that is currently in `data/matches.json`.
+    All,
+}
+
+/// Rebuilds the summary and the enriched events of archived matches with the
+/// current enrichment code, replacing the stored files and Redis keys.
+pub async fn run(selection: Selection) -> Result<(), FetchError> {
+    let index = ArchiveIndex::load()?;
+    let mut match_ids: Vec<String> = match selection {
+        Selection::Match(match_id) => vec![match_id],
+        Selection::All => {
+            let stored = stored_match_ids();
+            index.matches.keys().filter(|match_id| stored.contains(match_id)).cloned().collect()
+        }
+    };
+    // sama järjestys kuin alkuperäisessä ingestissä
+    match_ids.sort_by_key(|match_id| index.matches.get(match_id).map(|entry| entry.created_at.clone()));
+
+    if match_ids.is_empty() {
+        println!("No archived matches to reprocess");
+        return Ok(());
+    }
+
+    let mut status = IngestStatus::load().map_err(|e| FetchError::storage("reading ingest status", e.to_string()))?;
+    let mut failed = 0;
+    for match_id in &match_ids {
+        let shard = index.matches.get(match_id).map(|entry| entry.shard.clone()).unwrap_or_default();
+        let rebuilt = match archive_response(match_id) {
+            Ok(response) => fetch_match_data(response, &shard, &TelemetrySource::Archive).await,
+            Err(e) => Err(e),
+        };
+
+        match rebuilt {
+            Ok(()) => {
+                println!("Reprocessed match {}", match_id);
+                status.mark_done(match_id);
+            }
+            Err(e) => {
+                eprintln!("Failed to reprocess match {}: {}", match_id, e);
+                failed += 1;
+            }
+        }
+    }
+    if let Err(e) = status.save() {
+        eprintln!("Failed to save ingest status: {}", e);
+    }
+
+    println!("Reprocessed {} of {} matches", match_ids.len() - failed, match_ids.len());
+    if failed > 0 {
+        return Err(FetchError::storage("reprocessing archived matches", format!("{} of {} matches failed", failed, match_ids.len())));
+    }
+    Ok(())
+}
+
+fn archive_response(match_id: &str) -> Result<MatchResponse, FetchError> {
+    parse_match_response(&archive::read_match_response(match_id)?)
+}
@@ -0,0 +1,220 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use crate::identity;
+use crate::pubg_client::PubgClient;
+
+// PUBG hyväksyy max 10 nimeä yhteen filter[playerNames] hakuun
+const MAX_PLAYERS: usize = 10;
+const DEFAULT_PLAYERS_FILE: &str = "players.json";
+pub const DEFAULT_SHARD: &str = "steam";
+const SHARDS: [&str; 5] = ["steam", "kakao", "psn", "xbox", "console"];
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TrackedPlayer {
+    pub name: String,
+    #[serde(default = "default_shard")]
+    pub shard: String,
+    /// Optional, found through the name on the first lookup otherwise. Once
+    /// known the player is looked up by id, so a rename does not lose them.
+    #[serde(default)]
+    pub account_id: Option<String>,
+}
+
+fn default_shard() -> String {
+    DEFAULT_SHARD.to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct RosterFile {
+    players: Vec<TrackedPlayer>,
+}
+
+#[derive(Debug)]
+struct Roster {
+    players: Vec<TrackedPlayer>,
+    source: RosterSource,
+}
+
+#[derive(Debug)]
+enum RosterSource {
+    Env,
+    File { path: String, modified: Option<SystemTime> },
+}
+
+static ROSTER: RwLock<Option<Roster>> = RwLock::new(None);
+static SHARD_FILTER: RwLock<Option<String>> = RwLock::new(None);
+
+fn players_file() -> String {
+    env::var("PLAYERS_FILE").unwrap_or_else(|_| DEFAULT_PLAYERS_FILE.to_string())
+}
+
+fn file_modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn validate(players: &[TrackedPlayer]) -> Result<(), Box<dyn std::error::Error>> {
+    if players.is_empty() {
+        return Err("roster is empty".into());
+    }
+
+    let mut seen: HashSet<String> = HashSet::new();
+    for player in players {
+        let name = &player.name;
+        if name.len() < 3 || name.len() > 16 {
+            return Err(format!("player name {:?} must be 3-16 characters long", name).into());
+        }
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("player name {:?} contains characters PUBG does not allow", name).into());
+        }
+        if player.account_id.as_ref().is_some_and(|account_id| !account_id.starts_with("account.")) {
+            return Err(format!("player {:?} has account id {:?}, expected account.<id>", name, player.account_id).into());
+        }
+        if !SHARDS.contains(&player.shard.as_str()) {
+            return Err(format!("player {:?} has unknown shard {:?}, expected one of {:?}", name, player.shard, SHARDS).into());
+        }
+        if !seen.insert(format!("{}/{}", player.shard, name.to_lowercase())) {
+            return Err(format!("player name {:?} is listed twice", name).into());
+        }
+    }
+
+    for shard in SHARDS {
+        let count = players.iter().filter(|player| player.shard == shard).count();
+        if count > MAX_PLAYERS {
+            return Err(format!("shard {} has {} players, the API allows at most {}", shard, count, MAX_PLAYERS).into());
+        }
+    }
+    Ok(())
+}
+
+/// `PLAYER_NAMES=E1_Duderino,psn:SomeName`, players without a prefix are on steam.
+fn parse_env_roster(value: &str) -> Vec<TrackedPlayer> {
+    value
+        .split(',')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once(':') {
+            Some((shard, name)) => TrackedPlayer { name: name.to_string(), shard: shard.to_string(), account_id: None },
+            None => TrackedPlayer { name: entry.to_string(), shard: default_shard(), account_id: None },
+        })
+        .collect()
+}
+
+fn read_roster_file(path: &str) -> Result<Vec<TrackedPlayer>, Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read roster file {}: {}", path, e))?;
+    let parsed: RosterFile = serde_json::from_str(&contents)
+        .map_err(|e| format!("failed to parse roster file {}: {}", path, e))?;
+    Ok(parsed.players)
+}
+
+fn load() -> Result<Roster, Box<dyn std::error::Error>> {
+    if let Ok(value) = env::var("PLAYER_NAMES") {
+        let players = parse_env_roster(&value);
+        validate(&players)?;
+        return Ok(Roster { players, source: RosterSource::Env });
+    }
+
+    let path = players_file();
+    let modified = file_modified(&path);
+    let players = read_roster_file(&path)?;
+    validate(&players)?;
+    Ok(Roster { players, source: RosterSource::File { path, modified } })
+}
+
+/// Loads and validates the roster. Called once at startup, an invalid roster is fatal here.
+pub fn init() -> Result<(), Box<dyn std::error::Error>> {
+    let roster = load()?;
+    println!("Tracking {} players: {:?}", roster.players.len(), roster.players.iter().map(|p| format!("{}/{}", p.shard, p.name)).collect::<Vec<_>>());
+    *ROSTER.write().unwrap() = Some(roster);
+    Ok(())
+}
+
+/// Re-reads the roster file if it has changed since the last load.
+/// A broken file is reported and the previous roster is kept.
+pub fn reload_if_changed() {
+    let (path, loaded_modified) = match ROSTER.read().unwrap().as_ref() {
+        Some(Roster { source: RosterSource::File { path, modified }, .. }) => (path.clone(), *modified),
+        _ => return,
+    };
+
+    let modified = file_modified(&path);
+    if modified == loaded_modified {
+        return;
+    }
+
+    match read_roster_file(&path).and_then(|players| validate(&players).map(|_| players)) {
+        Ok(players) => {
+            println!("Roster file {} changed, now tracking {:?}", path, players.iter().map(|p| format!("{}/{}", p.shard, p.name)).collect::<Vec<_>>());
+            *ROSTER.write().unwrap() = Some(Roster { players, source: RosterSource::File { path, modified } });
+        }
+        Err(e) => {
+            eprintln!("Ignoring roster change: {}", e);
+            // ei yritetä samaa rikkinäistä tiedostoa uudestaan joka kierroksella
+            if let Some(Roster { source: RosterSource::File { modified: current, .. }, .. }) = ROSTER.write().unwrap().as_mut() {
+                *current = modified;
+            }
+        }
+    }
+}
+
+/// Limits [`players`] to the players on `shard`, for `--shard`.
+pub fn set_shard_filter(shard: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(shard) = &shard {
+        if !SHARDS.contains(&shard.as_str()) {
+            return Err(format!("unknown shard {:?}, expected one of {:?}", shard, SHARDS).into());
+        }
+    }
+    *SHARD_FILTER.write().unwrap() = shard;
+    Ok(())
+}
+
+pub fn players() -> Vec<TrackedPlayer> {
+    let shard_filter = SHARD_FILTER.read().unwrap().clone();
+    ROSTER
+        .read()
+        .unwrap()
+        .as_ref()
+        .map(|roster| roster.players.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|player| shard_filter.as_ref().is_none_or(|shard| player.shard == *shard))
+        .collect()
+}
+
+/// Shards that have at least one tracked player, in roster order.
+pub fn shards() -> Vec<String> {
+    let mut shards: Vec<String> = Vec::new();
+    for player in players() {
+        if !shards.contains(&player.shard) {
+            shards.push(player.shard);
+        }
+    }
+    shards
+}
+
+/// `/players` lookup of everyone on `shard`. By account id once every
+/// player's id is known, otherwise by name, using the current name of
+/// players that have been renamed since.
+pub fn make_player_id_url(pubg_client: &PubgClient, shard: &str) -> String {
+    let registry = identity::Registry::load().unwrap_or_default();
+    let players: Vec<TrackedPlayer> = players().into_iter().filter(|player| player.shard == shard).collect();
+    let account_ids: Vec<String> = players.iter().filter_map(|player| identity::account_id(&registry, player)).collect();
+
+    if account_ids.len() == players.len() {
+        return pubg_client.shard_url(shard, "players?filter[playerIds]=") + &account_ids.join("%2C");
+    }
+    let names: Vec<String> = players
+        .iter()
+        .map(|player| {
+            identity::account_id(&registry, player)
+                .and_then(|account_id| registry.current_name(&account_id).map(str::to_string))
+                .unwrap_or_else(|| player.name.clone())
+        })
+        .collect();
+    pubg_client.shard_url(shard, "players?filter[playerNames]=") + &names.join("%2C")
+}
@@ -0,0 +1,225 @@
+use chrono::{DateTime, Local, NaiveTime, Utc};
+use serde_json::Value;
+use std::fs;
+use std::str::FromStr;
+use std::time::Duration;
+
+use shared_utils::paths::data_path;
+
+// pelaaja on aktiivinen jos hänellä on tätä tuoreempi peli
+const ACTIVE_WINDOW: chrono::Duration = chrono::Duration::hours(1);
+
+/// A daily window, in the local time of the host, when nothing is polled.
+/// `02:00-08:00`, a window like `23:30-06:00` continues past midnight.
+#[derive(Debug, Clone, Copy)]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl FromStr for QuietHours {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|e| format!("invalid time {:?} in quiet hours {:?}: {}", time, value, e))
+        };
+        let (start, end) = value.split_once('-').ok_or_else(|| format!("quiet hours {:?} are not of the form HH:MM-HH:MM", value))?;
+        let quiet_hours = QuietHours { start: parse(start)?, end: parse(end)? };
+        if quiet_hours.start == quiet_hours.end {
+            return Err(format!("quiet hours {:?} start and end at the same time", value));
+        }
+        Ok(quiet_hours)
+    }
+}
+
+impl QuietHours {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    fn remaining(&self, time: NaiveTime) -> chrono::Duration {
+        let remaining = self.end - time;
+        if remaining <= chrono::Duration::zero() {
+            remaining + chrono::Duration::days(1)
+        } else {
+            remaining
+        }
+    }
+}
+
+/// When `poll` runs. With an idle interval the scheduler polls every
+/// `interval` while a tracked player has played in the last hour and every
+/// `idle_interval` otherwise. Each wait is moved by up to `jitter` either way
+/// so the polls don't line up with other clients of the API.
+#[derive(Debug)]
+pub struct Scheduler {
+    interval: Duration,
+    idle_interval: Option<Duration>,
+    jitter: Duration,
+    quiet_hours: Vec<QuietHours>,
+    idle: Option<bool>,
+}
+
+impl Scheduler {
+    pub fn new(interval: Duration, idle_interval: Option<Duration>, jitter: Duration, quiet_hours: Vec<QuietHours>) -> Self {
+        Scheduler { interval, idle_interval, jitter, quiet_hours, idle: None }
+    }
+
+    /// Time left of the quiet hours `now` falls in, if any.
+    pub fn quiet_remaining(&self, now: DateTime<Local>) -> Option<Duration> {
+        self.quiet_hours
+            .iter()
+            .filter(|quiet_hours| quiet_hours.contains(now.time()))
+            .map(|quiet_hours| quiet_hours.remaining(now.time()))
+            .max()
+            .and_then(|remaining| remaining.to_std().ok())
+    }
+
+    /// How long to wait before the next poll.
+    pub fn next_delay(&mut self) -> Duration {
+        let interval = self.interval(|| recently_played(Utc::now()));
+        jittered(interval, self.jitter)
+    }
+
+    // matches.json luetaan vain kun idle-väli on käytössä
+    fn interval(&mut self, recently_played: impl FnOnce() -> bool) -> Duration {
+        match self.idle_interval {
+            Some(idle_interval) => {
+                let idle = !recently_played();
+                if self.idle != Some(idle) {
+                    self.idle = Some(idle);
+                    if idle {
+                        println!("No matches in the last hour, polling every {}s", idle_interval.as_secs());
+                    } else {
+                        println!("Tracked players are playing, polling every {}s", self.interval.as_secs());
+                    }
+                }
+                if idle {
+                    idle_interval
+                } else {
+                    self.interval
+                }
+            }
+            None => self.interval,
+        }
+    }
+}
+
+fn jittered(interval: Duration, jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        return interval;
+    }
+    let jitter_ms = jitter.as_millis() as u64;
+    let offset = fastrand::u64(0..=2 * jitter_ms);
+    // ei koskaan nollaa, vaikka jitter olisi intervallia suurempi
+    (interval + Duration::from_millis(offset)).saturating_sub(jitter).max(Duration::from_secs(1))
+}
+
+/// Whether the newest stored match started within the last hour.
+/// `matches.json` only has matches of tracked players.
+fn recently_played(now: DateTime<Utc>) -> bool {
+    let Ok(contents) = fs::read_to_string(data_path("matches.json")) else {
+        return false;
+    };
+    let Ok(summaries) = serde_json::from_str::<Vec<Value>>(&contents) else {
+        return false;
+    };
+    played_within(&summaries, now)
+}
+
+fn played_within(summaries: &[Value], now: DateTime<Utc>) -> bool {
+    summaries
+        .iter()
+        .filter_map(|summary| summary.get("date")?.as_str())
+        .filter_map(|date| DateTime::parse_from_rfc3339(date).ok())
+        .any(|date| now - date.with_timezone(&Utc) < ACTIVE_WINDOW)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_quiet_hours() {
+        assert!("02:00-08:00".parse::<QuietHours>().is_ok());
+        assert!(" 23:30 - 06:00 ".parse::<QuietHours>().is_ok());
+        assert!("02:00".parse::<QuietHours>().is_err());
+        assert!("02:00-25:00".parse::<QuietHours>().is_err());
+        assert!("08:00-08:00".parse::<QuietHours>().is_err());
+    }
+
+    #[test]
+    fn quiet_hours_within_a_day() {
+        let quiet_hours: QuietHours = "02:00-08:00".parse().unwrap();
+        assert!(!quiet_hours.contains(time(1, 59)));
+        assert!(quiet_hours.contains(time(2, 0)));
+        assert!(quiet_hours.contains(time(7, 59)));
+        assert!(!quiet_hours.contains(time(8, 0)));
+        assert_eq!(quiet_hours.remaining(time(7, 30)), chrono::Duration::minutes(30));
+    }
+
+    #[test]
+    fn quiet_hours_past_midnight() {
+        let quiet_hours: QuietHours = "23:30-06:00".parse().unwrap();
+        assert!(quiet_hours.contains(time(23, 30)));
+        assert!(quiet_hours.contains(time(0, 0)));
+        assert!(quiet_hours.contains(time(5, 59)));
+        assert!(!quiet_hours.contains(time(6, 0)));
+        assert!(!quiet_hours.contains(time(12, 0)));
+        assert_eq!(quiet_hours.remaining(time(23, 45)), chrono::Duration::minutes(6 * 60 + 15));
+        assert_eq!(quiet_hours.remaining(time(1, 0)), chrono::Duration::hours(5));
+    }
+
+    #[test]
+    fn waits_for_the_longest_overlapping_quiet_hours() {
+        let quiet_hours = vec!["00:00-03:00".parse().unwrap(), "23:00-05:00".parse().unwrap()];
+        let scheduler = Scheduler::new(Duration::from_secs(60), None, Duration::ZERO, quiet_hours);
+        let night = Local.with_ymd_and_hms(2025, 5, 1, 1, 0, 0).unwrap();
+        assert_eq!(scheduler.quiet_remaining(night), Some(Duration::from_secs(4 * 3600)));
+        let day = Local.with_ymd_and_hms(2025, 5, 1, 12, 0, 0).unwrap();
+        assert_eq!(scheduler.quiet_remaining(day), None);
+    }
+
+    #[test]
+    fn polls_slower_when_nobody_plays() {
+        let fast = Duration::from_secs(30);
+        let slow = Duration::from_secs(600);
+        let mut scheduler = Scheduler::new(fast, Some(slow), Duration::ZERO, Vec::new());
+        assert_eq!(scheduler.interval(|| true), fast);
+        assert_eq!(scheduler.interval(|| false), slow);
+
+        let mut scheduler = Scheduler::new(fast, None, Duration::ZERO, Vec::new());
+        assert_eq!(scheduler.interval(|| unreachable!()), fast);
+    }
+
+    #[test]
+    fn recent_match_counts_as_playing() {
+        let now = Utc.with_ymd_and_hms(2025, 5, 1, 18, 0, 0).unwrap();
+        let summaries = vec![json!({"date": "2025-05-01T16:00:00Z"}), json!({"date": "2025-05-01T17:30:00Z"})];
+        assert!(played_within(&summaries, now));
+        assert!(!played_within(&summaries[..1], now));
+        assert!(!played_within(&[json!({})], now));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let interval = Duration::from_secs(60);
+        assert_eq!(jittered(interval, Duration::ZERO), interval);
+        for _ in 0..1000 {
+            let delay = jittered(interval, Duration::from_secs(10));
+            assert!(delay >= Duration::from_secs(50) && delay <= Duration::from_secs(70));
+            assert!(jittered(Duration::from_secs(2), Duration::from_secs(10)) >= Duration::from_secs(1));
+        }
+    }
+}
@@ -1,272 +1,345 @@
-use reqwest::{Client, header};
-use serde_json;
-use serde::{Serialize, Deserialize};
-use std::error::Error;
-use std::env;
-use dotenv::dotenv;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::Write;
-
-const PLAYER_NAMES: [&str; 4] = [
-    "E1_Duderino",
-    "keken_viikset",
-    "HlGHLANDER",
-    "bold_moves_bob",
-];
-
-fn player_id_merge () -> String {
-
-    let mut players_ids_merged = String::new();
-
-    for (index, name) in PLAYER_NAMES.iter().enumerate() {
-        if index == PLAYER_NAMES.len() -1 {
-            players_ids_merged.push_str(name);
-        } else {
-            players_ids_merged.push_str(name);
-            players_ids_merged.push_str("%2C");
-        }
-    }
-    return players_ids_merged;
-}
-
-fn make_player_id_url () -> String {
-    let mut player_url = String::new();
-    player_url.push_str("https://api.pubg.com/shards/steam/players?filter[playerNames]=");
-    let player_ids = player_id_merge();
-    player_url.push_str(&player_ids);
-    return player_url;
-}
-
-async fn fetch_player_url (api_key: String , season_id: String) -> Result<(), Box<dyn std::error::Error>> {
-    let player_ids_url = make_player_id_url();
-    let client = Client::new();
-
-    let mut headers = header::HeaderMap::new();
-    headers.insert(header::ACCEPT, "application/vnd.api+json".parse()?);
-    headers.insert(header::AUTHORIZATION, format!("Bearer {}", api_key).parse()?);
-
-    let response = client
-          .get(player_ids_url)
-          .headers(headers)
-          .send()
-          .await?
-          .text()
-          .await?;
-
-     extract_user_id(&response, api_key, season_id).await?;
-
-     Ok(())
-}
-
-#[derive(Debug, Deserialize)]
-struct PlayerIdData {
-    data: Vec<PlayerId>,
-}
-
-#[derive(Debug, Deserialize)]
-struct PlayerId {
-    id: String,
-}
-
-async fn extract_user_id(response: &String, api_key: String, season_id: String) -> Result<(), Box<dyn Error>> {
-    let parsed: PlayerIdData = serde_json::from_str(&response)?;
-
-    let mut player_ids: Vec<String> = Vec::new();
-
-        for player in parsed.data {
-            player_ids.push(player.id);
-        }
-
-        let combined_ids = combine_user_ids(player_ids);
-        fetch_season_data(&combined_ids, api_key, season_id).await?;
-
-        Ok(())
-}
-
-#[derive(Debug, Deserialize)]
-struct SeasonData {
-    data: Vec<PlayerData>,
-}
-
-#[derive(Debug, Deserialize)]
-struct PlayerData {
-    #[serde(rename = "type")]
-    type_: String,
-    attributes: PlayerAttributes,
-    relationships: Relationships
-}
-
-#[derive(Debug, Deserialize)]
-struct PlayerAttributes {
-    #[serde(rename = "gameModeStats")]
-        game_mode_stats: HashMap<String, GameModeStats>,
-}
-
-#[derive(Debug, Deserialize)]
-struct Relationships {
-    player: Player,
-}
-
-#[derive(Debug, Deserialize)]
-struct Player {
-    data: PlayerIdFromSeasonData,
-}
-
-#[derive(Debug, Deserialize)]
-struct PlayerIdFromSeasonData {
-    id: String,
-}
-
-#[derive(Serialize, Debug, Deserialize)]
-struct GameModeStats {
-    assists: u32,
-    boosts: u32,
-    #[serde(rename = "dBNOs")]
-        dbnos: u32,
-
-        #[serde(rename = "dailyKills")]
-        daily_kills: u32,
-
-        #[serde(rename = "dailyWins")]
-        daily_wins: u32,
-
-        #[serde(rename = "damageDealt")]
-        damage_dealt: f64,
-
-        days: u32,
-
-        #[serde(rename = "headshotKills")]
-        headshot_kills: u32,
-
-        heals: u32,
-        kills: u32,
-
-        #[serde(rename = "longestKill")]
-        longest_kill: f64,
-
-        #[serde(rename = "longestTimeSurvived")]
-        longest_time_survived: u32,
-
-        losses: u32,
-
-        #[serde(rename = "maxKillStreaks")]
-        max_kill_streaks: u32,
-
-        #[serde(rename = "mostSurvivalTime")]
-        most_survival_time: u32,
-
-        #[serde(rename = "rankPoints")]
-        rank_points: i32,
-
-        #[serde(rename = "rankPointsTitle")]
-        rank_points_title: String,
-
-        revives: u32,
-
-        #[serde(rename = "rideDistance")]
-        ride_distance: f64,
-
-        #[serde(rename = "roadKills")]
-        road_kills: u32,
-
-        #[serde(rename = "roundMostKills")]
-        round_most_kills: u32,
-
-        #[serde(rename = "roundsPlayed")]
-        rounds_played: u32,
-
-        suicides: u32,
-
-        #[serde(rename = "swimDistance")]
-        swim_distance: f64,
-
-        #[serde(rename = "teamKills")]
-        team_kills: u32,
-
-        #[serde(rename = "timeSurvived")]
-        time_survived: u32,
-
-        top10s: u32,
-
-        #[serde(rename = "vehicleDestroys")]
-        vehicle_destroys: u32,
-
-        #[serde(rename = "walkDistance")]
-        walk_distance: f64,
-
-        #[serde(rename = "weaponsAcquired")]
-        weapons_acquired: u32,
-
-        #[serde(rename = "weeklyKills")]
-        weekly_kills: u32,
-
-        #[serde(rename = "weeklyWins")]
-        weekly_wins: u32,
-
-        #[serde(rename = "winPoints")]
-        win_points: u32,
-
-        wins: u32,
-}
-
-async fn fetch_season_data (player_id_query_params: &str, api_key: String, season_id: String) -> Result<(), Box<dyn std::error::Error>> {
-    let client = Client::new();
-    let player_stat_url = "https://api.pubg.com/shards/steam/seasons/".to_owned() + &season_id + "/gameMode/squad-fpp/players?filter[playerIds]=" + &player_id_query_params;
-
-    let mut headers = header::HeaderMap::new();
-        headers.insert(header::ACCEPT, "application/vnd.api+json".parse()?);
-        headers.insert(header::AUTHORIZATION, format!("Bearer {}", api_key).parse()?);
-
-    let response = client
-        .get(player_stat_url)
-        .headers(headers)
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    let parsed: SeasonData = serde_json::from_str(&response)?;
-    let mut player_data_map: HashMap<String, Option<&GameModeStats>> = HashMap::new();
-    for (index, player_data) in parsed.data.iter().enumerate() {
-      //  let player_id = &player_data.relationships.player.data.id;
-        let player_season_data = &player_data.attributes;
-        let player_name = PLAYER_NAMES[index];
-        let player_stats = player_season_data.game_mode_stats.get("squad-fpp");
-        player_data_map.insert(player_name.to_string(), player_stats);
-    }
-    const FILENAME: &str = "test.json" ;
-    let _ = save_to_json(player_data_map, FILENAME);
-    Ok(())
-}
-
-fn save_to_json(data: HashMap<String, Option<&GameModeStats>>, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let json_data = serde_json::to_string_pretty(&data)?;
-    let mut file = File::create(filename)?;
-    file.write_all(json_data.as_bytes())?;
-    println!("success");
-    Ok(())
-}
-
-fn combine_user_ids(player_ids: Vec<String>) -> String {
-    let mut combined_ids = String::new();
-    for (index, id) in player_ids.iter().enumerate() {
-        if index == player_ids.len() - 1 {
-            combined_ids.push_str(id);
-        } else {
-            combined_ids.push_str(id);
-            combined_ids.push_str("%2C");
-        }
-    }
-    return combined_ids;
-}
-
-#[tokio::main]
-async fn main() {
-    dotenv().ok();
-    let api_key = env::var("api_key").expect("Api key not found...");
-    let season_id = env::var("season_id").expect("Season id not found...");
-    if let Err(e) = fetch_player_url(api_key, season_id).await {
-        eprintln!("Error {}", e);
-    }
-}
+use serde::{Serialize, Deserialize};
+use std::error::Error;
+use std::collections::{BTreeMap, HashMap};
+use chrono::Utc;
+use crate::identity;
+use crate::mastery;
+use crate::ranked;
+use crate::roster;
+use crate::pubg_client::PubgClient;
+use crate::season_history::{self, SnapshotStats};
+use crate::seasons::{self, Season};
+use crate::error::FetchError;
+use crate::write_atomic;
+use shared_utils::paths::data_path;
+use shared_utils::redis_loader::{load_player_past_season_to_redis, load_player_season_to_redis, load_player_stats_to_redis};
+
+/// Season stats of the tracked players. `season` picks a past season, by
+/// default the current season of each shard is used. Past seasons are kept
+/// apart in `season_stats/{season}/`, they never replace the current one.
+pub async fn fetch_player_url (pubg_client: &PubgClient, season: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut player_data_map: HashMap<String, (PlayerSeasonStats, Season)> = HashMap::new();
+    for shard in roster::shards() {
+        let season = seasons::resolve(pubg_client, &shard, season).await?;
+        println!("Fetching {} stats of season {}", shard, season.id);
+        let player_ids_url = roster::make_player_id_url(pubg_client, &shard);
+        let response = pubg_client.get(&player_ids_url).await?;
+
+        let players = extract_user_id(&response, &shard)?;
+        for (player_name, player_stats) in fetch_season_data(&players, pubg_client, &shard, &season.id).await? {
+            player_data_map.insert(player_name, (player_stats, season.clone()));
+        }
+
+        for (player_id, player_name) in &players {
+            if let Err(e) = ranked::refresh(pubg_client, &shard, &season, player_id, player_name, None).await {
+                eprintln!("Failed to fetch ranked stats of {}: {}", player_name, e);
+            }
+        }
+    }
+
+    for (player_name, (player_stats, season)) in &player_data_map {
+        if season.is_current_season {
+            save_to_json(player_name, player_stats)?;
+        } else {
+            save_past_season(player_name, player_stats)?;
+        }
+        season_history::record(player_name, season, snapshot_stats(player_stats))?;
+    }
+    Ok(())
+}
+
+// lifetime käyttää samaa polkua kuin kaudet, seasons/lifetime/gameMode/...
+const LIFETIME_SEASON: &str = "lifetime";
+
+/// Lifetime stats of every game mode plus the official weapon and survival
+/// mastery of the tracked players.
+pub async fn fetch_lifetime(pubg_client: &PubgClient) -> Result<(), Box<dyn std::error::Error>> {
+    for shard in roster::shards() {
+        println!("Fetching {} lifetime stats and mastery", shard);
+        let player_ids_url = roster::make_player_id_url(pubg_client, &shard);
+        let response = pubg_client.get(&player_ids_url).await?;
+
+        let players = extract_user_id(&response, &shard)?;
+        for (player_name, player_stats) in fetch_season_data(&players, pubg_client, &shard, LIFETIME_SEASON).await? {
+            let json_data = serde_json::to_string_pretty(&player_stats)?;
+            write_atomic(&data_path(&format!("lifetime_stats/{}.json", player_name)), json_data.as_bytes())?;
+            if let Err(e) = load_player_stats_to_redis("lifetime_stats", &player_name) {
+                eprintln!("Failed to load lifetime stats of {} into Redis: {}", player_name, e);
+            }
+        }
+
+        // yksi pyyntö per pelaaja, epäonnistuminen ei estä muiden hakua
+        for (player_id, player_name) in &players {
+            if let Err(e) = mastery::fetch_weapon_mastery(pubg_client, &shard, player_id, player_name).await {
+                eprintln!("Failed to fetch weapon mastery of {}: {}", player_name, e);
+            }
+            if let Err(e) = mastery::fetch_survival_mastery(pubg_client, &shard, player_id, player_name).await {
+                eprintln!("Failed to fetch survival mastery of {}: {}", player_name, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerIdData {
+    data: Vec<PlayerId>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerId {
+    id: String,
+    attributes: PlayerIdAttributes,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerIdAttributes {
+    name: String,
+}
+
+/// Player names by account id.
+fn extract_user_id(response: &str, shard: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let parsed: PlayerIdData = serde_json::from_str(response)?;
+
+    let mut players: HashMap<String, String> = HashMap::new();
+    for player in parsed.data {
+        players.insert(player.id, player.attributes.name);
+    }
+
+    let observed: Vec<(String, String)> = players.iter().map(|(id, name)| (id.clone(), name.clone())).collect();
+    if let Err(e) = identity::observe(shard, &observed) {
+        eprintln!("Failed to update the player registry: {}", e);
+    }
+    Ok(players)
+}
+
+// kaikki pelimuodot erikseen, /players haku palauttaa vain pyydetyn muodon
+const GAME_MODES: [&str; 6] = ["solo", "solo-fpp", "duo", "duo-fpp", "squad", "squad-fpp"];
+
+#[derive(Debug, Deserialize)]
+struct SeasonData {
+    data: Vec<PlayerData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerData {
+    attributes: PlayerAttributes,
+    relationships: Relationships
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerAttributes {
+    #[serde(rename = "gameModeStats")]
+        game_mode_stats: HashMap<String, GameModeStats>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Relationships {
+    player: Player,
+}
+
+#[derive(Debug, Deserialize)]
+struct Player {
+    data: PlayerIdFromSeasonData,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerIdFromSeasonData {
+    id: String,
+}
+
+/// One player's season in every game mode they have stats for.
+#[derive(Serialize, Debug)]
+struct PlayerSeasonStats {
+    name: String,
+    account_id: String,
+    shard: String,
+    season_id: String,
+    updated_at: String,
+    game_modes: BTreeMap<String, GameModeStats>,
+}
+
+#[derive(Serialize, Debug, Deserialize)]
+struct GameModeStats {
+    assists: u32,
+    boosts: u32,
+    #[serde(rename = "dBNOs")]
+        dbnos: u32,
+
+        #[serde(rename = "dailyKills")]
+        daily_kills: u32,
+
+        #[serde(rename = "dailyWins")]
+        daily_wins: u32,
+
+        #[serde(rename = "damageDealt")]
+        damage_dealt: f64,
+
+        days: u32,
+
+        #[serde(rename = "headshotKills")]
+        headshot_kills: u32,
+
+        heals: u32,
+        kills: u32,
+
+        #[serde(rename = "longestKill")]
+        longest_kill: f64,
+
+        #[serde(rename = "longestTimeSurvived")]
+        longest_time_survived: f64,
+
+        losses: u32,
+
+        #[serde(rename = "maxKillStreaks")]
+        max_kill_streaks: u32,
+
+        #[serde(rename = "mostSurvivalTime")]
+        most_survival_time: f64,
+
+        #[serde(rename = "rankPoints")]
+        rank_points: i32,
+
+        #[serde(rename = "rankPointsTitle")]
+        rank_points_title: String,
+
+        revives: u32,
+
+        #[serde(rename = "rideDistance")]
+        ride_distance: f64,
+
+        #[serde(rename = "roadKills")]
+        road_kills: u32,
+
+        #[serde(rename = "roundMostKills")]
+        round_most_kills: u32,
+
+        #[serde(rename = "roundsPlayed")]
+        rounds_played: u32,
+
+        suicides: u32,
+
+        #[serde(rename = "swimDistance")]
+        swim_distance: f64,
+
+        #[serde(rename = "teamKills")]
+        team_kills: u32,
+
+        #[serde(rename = "timeSurvived")]
+        time_survived: f64,
+
+        top10s: u32,
+
+        #[serde(rename = "vehicleDestroys")]
+        vehicle_destroys: u32,
+
+        #[serde(rename = "walkDistance")]
+        walk_distance: f64,
+
+        #[serde(rename = "weaponsAcquired")]
+        weapons_acquired: u32,
+
+        #[serde(rename = "weeklyKills")]
+        weekly_kills: u32,
+
+        #[serde(rename = "weeklyWins")]
+        weekly_wins: u32,
+
+        #[serde(rename = "winPoints")]
+        win_points: u32,
+
+        wins: u32,
+}
+
+async fn fetch_season_data (players: &HashMap<String, String>, pubg_client: &PubgClient, shard: &str, season_id: &str) -> Result<HashMap<String, PlayerSeasonStats>, Box<dyn std::error::Error>> {
+    let player_id_query_params = combine_user_ids(players.keys().cloned().collect());
+    let mut player_data_map: HashMap<String, PlayerSeasonStats> = HashMap::new();
+
+    for game_mode in GAME_MODES {
+        let player_stat_url = pubg_client.shard_url(shard, "seasons/") + season_id + "/gameMode/" + game_mode + "/players?filter[playerIds]=" + &player_id_query_params;
+        let response = match pubg_client.get(&player_stat_url).await {
+            Ok(response) => response,
+            // 404 = kukaan ei ole pelannut tätä muotoa tällä kaudella
+            Err(FetchError::Status { status: 404, .. }) => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let parsed: SeasonData = serde_json::from_str(&response)?;
+        for mut player_data in parsed.data {
+            // nimi haetaan id:n perusteella, API ei palauta pelaajia samassa järjestyksessä
+            let player_id = player_data.relationships.player.data.id;
+            let Some(player_name) = players.get(&player_id) else {
+                eprintln!("Season stats for unknown player {}", player_id);
+                continue;
+            };
+            let Some(player_stats) = player_data.attributes.game_mode_stats.remove(game_mode) else { continue };
+
+            player_data_map
+                .entry(player_name.to_string())
+                .or_insert_with(|| PlayerSeasonStats {
+                    name: player_name.to_string(),
+                    account_id: player_id.clone(),
+                    shard: shard.to_string(),
+                    season_id: season_id.to_string(),
+                    updated_at: Utc::now().to_rfc3339(),
+                    game_modes: BTreeMap::new(),
+                })
+                .game_modes
+                .insert(game_mode.to_string(), player_stats);
+        }
+    }
+    Ok(player_data_map)
+}
+
+fn snapshot_stats(player_stats: &PlayerSeasonStats) -> BTreeMap<String, SnapshotStats> {
+    player_stats
+        .game_modes
+        .iter()
+        .map(|(game_mode, stats)| {
+            (game_mode.clone(), SnapshotStats {
+                kills: stats.kills,
+                damage_dealt: stats.damage_dealt,
+                wins: stats.wins,
+                rank_points: stats.rank_points as f64,
+                top10s: stats.top10s,
+                rounds_played: stats.rounds_played,
+            })
+        })
+        .collect()
+}
+
+/// Writes `data/season_stats/{name}.json` and loads it into Redis.
+fn save_to_json(player_name: &str, player_stats: &PlayerSeasonStats) -> Result<(), Box<dyn std::error::Error>> {
+    let json_data = serde_json::to_string_pretty(player_stats)?;
+    write_atomic(&data_path(&format!("season_stats/{}.json", player_name)), json_data.as_bytes())?;
+
+    // tiedosto on tallessa, redis-palvelin lataa sen käynnistyessä jos tämä epäonnistuu
+    if let Err(e) = load_player_season_to_redis(player_name) {
+        eprintln!("Failed to load season stats of {} into Redis: {}", player_name, e);
+    }
+    Ok(())
+}
+
+/// Writes `data/season_stats/{season}/{name}.json` and loads it into Redis.
+fn save_past_season(player_name: &str, player_stats: &PlayerSeasonStats) -> Result<(), Box<dyn std::error::Error>> {
+    let json_data = serde_json::to_string_pretty(player_stats)?;
+    write_atomic(&data_path(&format!("season_stats/{}/{}.json", player_stats.season_id, player_name)), json_data.as_bytes())?;
+
+    if let Err(e) = load_player_past_season_to_redis(&player_stats.season_id, player_name) {
+        eprintln!("Failed to load season {} stats of {} into Redis: {}", player_stats.season_id, player_name, e);
+    }
+    Ok(())
+}
+
+fn combine_user_ids(player_ids: Vec<String>) -> String {
+    let mut combined_ids = String::new();
+    for (index, id) in player_ids.iter().enumerate() {
+        if index == player_ids.len() - 1 {
+            combined_ids.push_str(id);
+        } else {
+            combined_ids.push_str(id);
+            combined_ids.push_str("%2C");
+        }
+    }
+    combined_ids
+}
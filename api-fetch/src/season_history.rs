@@ -0,0 +1,66 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::error::FetchError;
+use crate::seasons::Season;
+use crate::write_atomic;
+use shared_utils::paths::data_path;
+use shared_utils::redis_loader::load_player_season_history_to_redis;
+
+/// The season stats we chart over time, for one game mode.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotStats {
+    pub kills: u32,
+    pub damage_dealt: f64,
+    pub wins: u32,
+    pub rank_points: f64,
+    pub top10s: u32,
+    pub rounds_played: u32,
+}
+
+/// A player's season stats at the end of one day.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    /// UTC date, `YYYY-MM-DD`.
+    pub date: String,
+    pub season_id: String,
+    pub game_modes: BTreeMap<String, SnapshotStats>,
+}
+
+fn history_path(player_name: &str) -> std::path::PathBuf {
+    data_path(&format!("season_history/{}.json", player_name))
+}
+
+fn load(player_name: &str) -> Result<Vec<Snapshot>, FetchError> {
+    match fs::read_to_string(history_path(player_name)) {
+        Ok(contents) => serde_json::from_str(&contents).map_err(|e| FetchError::json(format!("season history of {}", player_name), e)),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// Appends today's snapshot to `data/season_history/{name}.json`. Running
+/// again on the same day replaces that day's snapshot, so the series has one
+/// point per day with the latest stats of the day. Only the current season
+/// is recorded, a past season's totals are not today's.
+pub fn record(player_name: &str, season: &Season, game_modes: BTreeMap<String, SnapshotStats>) -> Result<(), FetchError> {
+    if !season.is_current_season {
+        return Ok(());
+    }
+    let season_id = season.id.as_str();
+    let mut history = load(player_name)?;
+    let date = Utc::now().format("%Y-%m-%d").to_string();
+    // kauden vaihtuessa samana päivänä molemmat kaudet jäävät talteen
+    history.retain(|snapshot| snapshot.date != date || snapshot.season_id != season_id);
+    history.push(Snapshot { date, season_id: season_id.to_string(), game_modes });
+
+    let json_data = serde_json::to_string_pretty(&history).map_err(|e| FetchError::json("season history", e))?;
+    write_atomic(&history_path(player_name), json_data.as_bytes())?;
+
+    if let Err(e) = load_player_season_history_to_redis(player_name) {
+        eprintln!("Failed to load season history of {} into Redis: {}", player_name, e);
+    }
+    Ok(())
+}
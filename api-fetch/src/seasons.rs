@@ -0,0 +1,131 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::error::FetchError;
+use crate::pubg_client::PubgClient;
+use crate::write_atomic;
+use shared_utils::paths::data_path;
+
+const CACHE_FILE: &str = "seasons.json";
+// PUBG pyytää hakemaan kausilistan harvoin, se muuttuu vain kauden vaihtuessa
+const CACHE_HOURS: i64 = 24;
+
+#[derive(Debug, Deserialize)]
+struct SeasonsResponse {
+    data: Vec<SeasonData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SeasonData {
+    id: String,
+    attributes: SeasonAttributes,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SeasonAttributes {
+    is_current_season: bool,
+    is_offseason: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Season {
+    pub id: String,
+    pub is_current_season: bool,
+    pub is_offseason: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedSeasons {
+    fetched_at: String,
+    seasons: Vec<Season>,
+}
+
+/// `data/seasons.json`, the `/seasons` list of every shard.
+fn load_cache() -> BTreeMap<String, CachedSeasons> {
+    fs::read_to_string(data_path(CACHE_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(cache: &BTreeMap<String, CachedSeasons>) -> Result<(), FetchError> {
+    let json_data = serde_json::to_string_pretty(cache).map_err(|e| FetchError::json(CACHE_FILE, e))?;
+    write_atomic(&data_path(CACHE_FILE), json_data.as_bytes())
+}
+
+fn is_fresh(cached: &CachedSeasons) -> bool {
+    DateTime::parse_from_rfc3339(&cached.fetched_at)
+        .map(|fetched_at| Utc::now() - fetched_at.with_timezone(&Utc) < Duration::hours(CACHE_HOURS))
+        .unwrap_or(false)
+}
+
+async fn fetch_seasons(pubg_client: &PubgClient, shard: &str) -> Result<Vec<Season>, FetchError> {
+    let response = pubg_client.get(&pubg_client.shard_url(shard, "seasons")).await?;
+    let parsed: SeasonsResponse = serde_json::from_str(&response).map_err(|e| FetchError::json(format!("seasons of {}", shard), e))?;
+    Ok(parsed
+        .data
+        .into_iter()
+        .map(|season| Season {
+            id: season.id,
+            is_current_season: season.attributes.is_current_season,
+            is_offseason: season.attributes.is_offseason,
+        })
+        .collect())
+}
+
+/// Seasons of `shard` in the order the API lists them, oldest first.
+/// Served from the cache for a day unless `refresh` is set. If the API is
+/// down, an outdated cache is better than nothing.
+pub async fn list(pubg_client: &PubgClient, shard: &str, refresh: bool) -> Result<Vec<Season>, FetchError> {
+    let mut cache = load_cache();
+    if let Some(cached) = cache.get(shard) {
+        if !refresh && is_fresh(cached) {
+            return Ok(cached.seasons.clone());
+        }
+    }
+
+    match fetch_seasons(pubg_client, shard).await {
+        Ok(seasons) => {
+            cache.insert(shard.to_string(), CachedSeasons { fetched_at: Utc::now().to_rfc3339(), seasons: seasons.clone() });
+            if let Err(e) = save_cache(&cache) {
+                eprintln!("Failed to cache seasons: {}", e);
+            }
+            Ok(seasons)
+        }
+        Err(e) => match cache.remove(shard) {
+            Some(cached) => {
+                eprintln!("Fetching seasons of {} failed, using the list from {}: {}", shard, cached.fetched_at, e);
+                Ok(cached.seasons)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+fn find<'a>(seasons: &'a [Season], selected: Option<&str>) -> Option<&'a Season> {
+    match selected {
+        // pelkkä loppuosa riittää, esim. "pc-2018-34" tai "34"
+        Some(selected) => seasons
+            .iter()
+            .find(|season| season.id == selected || season.id.ends_with(&format!("-{}", selected))),
+        None => seasons.iter().find(|season| season.is_current_season),
+    }
+}
+
+/// The `selected` season on `shard`, or the current season when nothing is
+/// selected. A season missing from the cached list triggers one refresh, so
+/// a season that just started is found right away.
+pub async fn resolve(pubg_client: &PubgClient, shard: &str, selected: Option<&str>) -> Result<Season, FetchError> {
+    let seasons = list(pubg_client, shard, false).await?;
+    if let Some(season) = find(&seasons, selected) {
+        return Ok(season.clone());
+    }
+
+    let seasons = list(pubg_client, shard, true).await?;
+    find(&seasons, selected)
+        .cloned()
+        .ok_or_else(|| FetchError::UnknownSeason { shard: shard.to_string(), season: selected.map(str::to_string) })
+}
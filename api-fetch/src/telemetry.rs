@@ -0,0 +1,371 @@
+use chrono::{DateTime, Duration, FixedOffset};
+use reqwest::{header, Client};
+use serde::de::{DeserializeSeed, Deserializer, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::constants;
+use crate::error::FetchError;
+use crate::identity::TrackedPlayers;
+use crate::ingest_status::stored_match_ids;
+use crate::landing::Landings;
+use crate::movement::Movement;
+use crate::telemetry_events::{self, TelemetryEvent};
+use crate::write_atomic;
+use crate::zones::Zones;
+use crate::{ActionType, AttackWeapon, Event, Target};
+use shared_utils::paths::data_path;
+
+// osuma tulee yleensä sekunneissa, kranaatit ja molotovit vähän myöhemmin
+const ATTACK_WINDOW_SECONDS: i64 = 120;
+
+/// The parts of one attack id the enrichment uses.
+pub struct Attack {
+    /// Weapon of the first `LogPlayerAttack`.
+    pub weapon: Option<AttackWeapon>,
+    pub fire_weapon_stack_count: Option<i32>,
+    /// `_D` of the attack and of every damage it did.
+    pub times: Vec<Option<String>>,
+    attacked: bool,
+    tracked: bool,
+}
+
+/// Everything the enrichment needs from a telemetry file, collected while the
+/// events are streamed through [`stream_events`]. Only the events that end up
+/// in `data/matches/{id}.json` are kept whole, the rest are reduced to the
+/// few fields the enrichment looks at.
+pub struct TelemetryCollector {
+    tracked: TrackedPlayers,
+    /// Account ids by team id, from `LogPlayerCreate`.
+    pub squads: HashMap<i32, Vec<String>>,
+    /// `_D` of `LogMatchDefinition`, event times are relative to this.
+    pub match_start_time: String,
+    /// Account id of the finisher of every `LogPlayerKillV2` with attack id -1.
+    pub finisher_kills: Vec<String>,
+    /// Armor equips and pickups, item ids already renamed.
+    pub armor_equips: Vec<Event>,
+    /// Damage, armor destroy and kill events involving a tracked player.
+    pub tracked_events: Vec<Event>,
+    /// Attacks by or damaging a tracked player. Other attacks are dropped
+    /// once they are `ATTACK_WINDOW_SECONDS` old, so only the recent ones
+    /// are kept in memory.
+    pub attacks: HashMap<i32, Attack>,
+    /// Attack ids in the order they were first seen, for the dropping.
+    recent_attacks: VecDeque<(DateTime<FixedOffset>, i32)>,
+    /// Event types and fields the telemetry model does not know.
+    pub unknown: Unknowns,
+    /// Locations of the players in a tracked squad, for the paths.
+    pub movement: Movement,
+    /// Jumps, landings and deaths of every player, for the drop analysis.
+    pub landings: Landings,
+    /// Zone states, and positions and blue zone damage of the tracked
+    /// players, for the zone timeline.
+    pub zones: Zones,
+}
+
+impl TelemetryCollector {
+    pub fn new(tracked: TrackedPlayers) -> Self {
+        let movement = Movement::new(tracked.clone());
+        let zones = Zones::new(tracked.clone());
+        TelemetryCollector {
+            tracked,
+            squads: HashMap::new(),
+            match_start_time: String::new(),
+            finisher_kills: Vec::new(),
+            armor_equips: Vec::new(),
+            tracked_events: Vec::new(),
+            attacks: HashMap::new(),
+            recent_attacks: VecDeque::new(),
+            unknown: Unknowns::default(),
+            movement,
+            landings: Landings::default(),
+            zones,
+        }
+    }
+
+    fn is_tracked(&self, target: &Target) -> bool {
+        self.tracked.contains(&target.account_id, &target.name)
+    }
+
+    fn damage_involves_tracked(&self, event: &Event) -> bool {
+        [&event.attacker, &event.victim].into_iter().flatten().any(|target| self.is_tracked(target))
+    }
+
+    // vain ensimmäinen olemassa oleva rooli ratkaisee, kuten ennenkin
+    fn kill_involves_tracked(&self, event: &Event) -> bool {
+        [&event.attacker, &event.victim, &event.dbno_maker, &event.finisher, &event.killer]
+            .into_iter()
+            .flatten()
+            .next()
+            .is_some_and(|target| self.is_tracked(target))
+    }
+
+    fn attack(&mut self, attack_id: i32, time: Option<DateTime<FixedOffset>>) -> &mut Attack {
+        if let Some(time) = time {
+            self.forget_old_attacks(time);
+            if !self.attacks.contains_key(&attack_id) {
+                self.recent_attacks.push_back((time, attack_id));
+            }
+        }
+        self.attacks.entry(attack_id).or_insert_with(|| Attack {
+            weapon: None,
+            fire_weapon_stack_count: None,
+            times: Vec::new(),
+            attacked: false,
+            tracked: false,
+        })
+    }
+
+    fn forget_old_attacks(&mut self, now: DateTime<FixedOffset>) {
+        let window = Duration::seconds(ATTACK_WINDOW_SECONDS);
+        while let Some(&(time, attack_id)) = self.recent_attacks.front() {
+            if now - time < window {
+                break;
+            }
+            self.recent_attacks.pop_front();
+            if self.attacks.get(&attack_id).is_some_and(|attack| !attack.tracked) {
+                self.attacks.remove(&attack_id);
+            }
+        }
+    }
+
+    fn visit(&mut self, event: TelemetryEvent) {
+        self.movement.visit(&event);
+        self.landings.visit(&event);
+        self.zones.visit(&event);
+        match event {
+            TelemetryEvent::Other(unknown) => *self.unknown.events.entry(unknown.kind).or_default() += 1,
+            TelemetryEvent::Malformed(malformed) => *self.unknown.malformed.entry(malformed.kind).or_default() += 1,
+            event => {
+                for field in event.unknown_fields() {
+                    *self.unknown.fields.entry(field).or_default() += 1;
+                }
+                if let Some(event) = event.into_enrichment_event() {
+                    self.visit_event(event);
+                }
+            }
+        }
+    }
+
+    fn visit_event(&mut self, mut event: Event) {
+        match event.action {
+            Some(ActionType::LogPlayerCreate) => {
+                if let Some(player) = &event.player {
+                    self.squads.entry(player.team_id).or_default().push(player.account_id.to_string());
+                }
+            }
+            Some(ActionType::LogMatchDefinition) => {
+                if let Some(time) = &event.event_time {
+                    self.match_start_time = time.to_string();
+                }
+            }
+            Some(ActionType::LogPlayerAttack) => {
+                if let Some(attack_id) = event.attack_id {
+                    let tracked = event.attacker.as_ref().is_some_and(|attacker| self.is_tracked(attacker));
+                    let attack = self.attack(attack_id, event_time(&event));
+                    attack.times.push(event.event_time.clone());
+                    attack.tracked |= tracked;
+                    if !attack.attacked {
+                        attack.attacked = true;
+                        attack.weapon = event.weapon.take();
+                        attack.fire_weapon_stack_count = event.fire_weapon_stack_count;
+                    }
+                }
+            }
+            Some(ActionType::LogPlayerTakeDamage) => {
+                let tracked = self.damage_involves_tracked(&event);
+                if let Some(attack_id) = event.attack_id {
+                    let attack = self.attack(attack_id, event_time(&event));
+                    attack.times.push(event.event_time.clone());
+                    attack.tracked |= tracked;
+                }
+                if tracked {
+                    self.tracked_events.push(event);
+                }
+            }
+            Some(ActionType::LogArmorDestroy) if self.damage_involves_tracked(&event) => {
+                self.tracked_events.push(event);
+            }
+            Some(ActionType::LogPlayerKillV2) => {
+                if let (Some(finisher), Some(-1)) = (&event.finisher, event.attack_id) {
+                    self.finisher_kills.push(finisher.account_id.clone());
+                }
+                if self.kill_involves_tracked(&event) {
+                    self.tracked_events.push(event);
+                }
+            }
+            Some(ActionType::LogItemEquip) | Some(ActionType::LogItemPickupFromCarepackage) | Some(ActionType::LogItemPickupFromLootbox) => {
+                if let Some(ref mut armor) = event.item {
+                    if let Some((_, new_armor_name)) = constants::armors::ARMORS
+                        .iter()
+                        .find(|&&(key, _)| key == armor.item_id)
+                    {
+                        armor.item_id = new_armor_name.to_string();
+                        self.armor_equips.push(event);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn event_time(event: &Event) -> Option<DateTime<FixedOffset>> {
+    event.event_time.as_deref().and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+}
+
+/// Hands the elements of the top level telemetry array to the collector one
+/// at a time instead of building a `Vec<Event>` of the whole match.
+struct EventStream<'a>(&'a mut TelemetryCollector);
+
+impl<'de> DeserializeSeed<'de> for EventStream<'_> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for EventStream<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of telemetry events")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        while let Some(value) = seq.next_element::<Value>()? {
+            self.0.visit(telemetry_events::parse(value));
+        }
+        Ok(())
+    }
+}
+
+/// Parses the telemetry in `reader` in a single pass.
+pub fn stream_events<R: Read>(reader: R, match_id: &str, collector: &mut TelemetryCollector) -> Result<(), FetchError> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    EventStream(collector)
+        .deserialize(&mut deserializer)
+        .and_then(|_| deserializer.end())
+        .map_err(|e| FetchError::json(format!("telemetry of match {}", match_id), e))
+}
+
+const UNKNOWN_EVENTS_FILE: &str = "unknown_events.json";
+
+/// What the telemetry of one match had that the model does not know.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Unknowns {
+    /// Events by `_T`.
+    pub events: BTreeMap<String, usize>,
+    /// Events of a known type with a field the model does not know, by
+    /// `_T.path.field`.
+    #[serde(default)]
+    pub fields: BTreeMap<String, usize>,
+    /// Events of a known type that did not fit the model and were skipped,
+    /// by `_T`.
+    #[serde(default)]
+    pub malformed: BTreeMap<String, usize>,
+}
+
+impl Unknowns {
+    fn is_empty(&self) -> bool {
+        self.events.is_empty() && self.fields.is_empty() && self.malformed.is_empty()
+    }
+}
+
+/// Unknown telemetry of one match, in `data/unknown_events.json`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnknownEvents {
+    pub match_id: String,
+    pub seen_at: String,
+    #[serde(flatten)]
+    pub unknown: Unknowns,
+}
+
+pub fn load_unknown_events() -> Result<Vec<UnknownEvents>, FetchError> {
+    match fs::read_to_string(data_path(UNKNOWN_EVENTS_FILE)) {
+        Ok(contents) => serde_json::from_str(&contents).map_err(|e| FetchError::json(UNKNOWN_EVENTS_FILE, e)),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// Reports the event types and fields of `match_id` that the telemetry model
+/// does not know, so PUBG changes get noticed, and keeps the counts in
+/// `data/unknown_events.json` for `status`.
+pub fn report_unknown_events(match_id: &str, unknown: &Unknowns) -> Result<(), FetchError> {
+    let listed = |counts: &BTreeMap<String, usize>| counts.iter().map(|(name, count)| format!("{} x{}", name, count)).collect::<Vec<String>>().join(", ");
+    if !unknown.events.is_empty() {
+        println!("Match {} has unknown telemetry events: {}", match_id, listed(&unknown.events));
+    }
+    if !unknown.fields.is_empty() {
+        println!("Match {} has unknown telemetry fields: {}", match_id, listed(&unknown.fields));
+    }
+    if !unknown.malformed.is_empty() {
+        println!("Match {} has malformed telemetry events: {}", match_id, listed(&unknown.malformed));
+    }
+
+    let mut reports = load_unknown_events()?;
+    let had_report = reports.iter().any(|report| report.match_id == match_id);
+    if unknown.is_empty() && !had_report {
+        return Ok(());
+    }
+
+    // poistuneiden matsien raportit siivotaan samalla
+    let stored = stored_match_ids();
+    reports.retain(|report| report.match_id != match_id && stored.contains(&report.match_id));
+    if !unknown.is_empty() {
+        reports.push(UnknownEvents {
+            match_id: match_id.to_string(),
+            seen_at: chrono::Utc::now().to_rfc3339(),
+            unknown: unknown.clone(),
+        });
+    }
+
+    let json_data = serde_json::to_string_pretty(&reports).map_err(|e| FetchError::json(UNKNOWN_EVENTS_FILE, e))?;
+    write_atomic(&data_path(UNKNOWN_EVENTS_FILE), json_data.as_bytes())
+}
+
+/// Downloads the telemetry into `data/tmp/` chunk by chunk so the whole body
+/// never has to fit in memory. The caller removes the file when done.
+pub async fn download_to_temp_file(url: &str, match_id: &str) -> Result<PathBuf, FetchError> {
+    let client = Client::new();
+    let mut headers = header::HeaderMap::new();
+    headers.insert(header::ACCEPT, header::HeaderValue::from_static("application/vnd.api+json"));
+    headers.insert(header::ACCEPT_ENCODING, header::HeaderValue::from_static("gzip"));
+    let mut response = client
+        .get(url)
+        .headers(headers)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let folder = data_path("tmp");
+    fs::create_dir_all(&folder).map_err(|e| FetchError::storage(format!("creating {}", folder.display()), e))?;
+    let path = folder.join(format!("{}.telemetry.json", match_id));
+    let mut file = File::create(&path).map_err(|e| FetchError::storage(format!("creating {}", path.display()), e))?;
+
+    let written: Result<(), FetchError> = async {
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).map_err(|e| FetchError::storage(format!("writing {}", path.display()), e))?;
+        }
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = written {
+        let _ = fs::remove_file(&path);
+        return Err(e);
+    }
+    Ok(path)
+}
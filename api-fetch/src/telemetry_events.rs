@@ -0,0 +1,906 @@
+//! Typed model of the PUBG telemetry schema, one struct per `_T`.
+//!
+//! The ten events the enrichment writes to `data/matches/{id}.json` keep the
+//! flat [`Event`] they have always had, everything else gets its own struct
+//! built from the shared objects below. Fields the API leaves out of older
+//! matches fall back to their defaults instead of failing the whole file,
+//! fields it adds end up in `extra` and are reported per match.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+
+use crate::{ActionType, Event, Location};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Common {
+    pub is_game: f32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Character {
+    pub name: String,
+    pub team_id: i32,
+    pub health: f32,
+    pub location: Location,
+    pub ranking: i32,
+    pub individual_ranking: i32,
+    pub account_id: String,
+    pub is_in_blue_zone: bool,
+    pub is_in_red_zone: bool,
+    pub is_in_vehicle: bool,
+    pub zone: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Entry of `characters` in `LogMatchStart` and `LogMatchEnd`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CharacterWrapper {
+    pub character: Option<Character>,
+    pub primary_weapon_first: String,
+    pub primary_weapon_second: String,
+    pub secondary_weapon: String,
+    pub spawn_kit_index: i32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Item {
+    pub item_id: String,
+    pub stack_count: i32,
+    pub category: String,
+    pub sub_category: String,
+    pub attached_items: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ItemPackage {
+    pub item_package_id: String,
+    pub location: Location,
+    pub items: Vec<Item>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Vehicle {
+    pub vehicle_type: String,
+    pub vehicle_id: String,
+    pub vehicle_unique_id: i64,
+    pub health_percent: f32,
+    // API:n kirjoitusvirhe, kenttä on oikeasti "feulPercent"
+    #[serde(rename = "feulPercent")]
+    pub fuel_percent: f32,
+    pub altitude_abs: f32,
+    pub altitude_rel: f32,
+    pub velocity: f32,
+    pub seat_index: i32,
+    pub is_wheels_in_air: bool,
+    pub is_in_water_volume: bool,
+    pub is_engine_on: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Zones and head counts, `LogGameStatePeriodic` has one every ten seconds
+/// or so. Radii are in centimeters like the positions.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GameState {
+    pub elapsed_time: i32,
+    pub num_alive_teams: i32,
+    pub num_join_players: i32,
+    pub num_start_players: i32,
+    pub num_alive_players: i32,
+    pub safety_zone_position: Location,
+    pub safety_zone_radius: f32,
+    pub poison_gas_warning_position: Location,
+    pub poison_gas_warning_radius: f32,
+    pub red_zone_position: Location,
+    pub red_zone_radius: f32,
+    pub black_zone_position: Location,
+    pub black_zone_radius: f32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GameResult {
+    pub rank: i32,
+    pub game_result: String,
+    pub team_id: i32,
+    pub account_id: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GameResultOnFinished {
+    pub results: Vec<GameResult>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogMatchStart {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub map_name: String,
+    pub weather_id: String,
+    pub characters: Vec<CharacterWrapper>,
+    pub camera_view_behaviour: String,
+    pub team_size: i32,
+    pub is_custom_game: bool,
+    pub is_event_mode: bool,
+    pub blue_zone_custom_options: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogMatchEnd {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub characters: Vec<CharacterWrapper>,
+    pub game_result_on_finished: Option<GameResultOnFinished>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogGameStatePeriodic {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub game_state: Option<GameState>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogPhaseChange {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub phase: i32,
+    pub elapsed_time: f32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogPlayerPosition {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub character: Option<Character>,
+    pub vehicle: Option<Vehicle>,
+    pub elapsed_time: f32,
+    pub num_alive_players: i32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogPlayerLogin {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub account_id: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogPlayerLogout {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub account_id: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogParachuteLanding {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub character: Option<Character>,
+    pub distance: f32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogVehicleRide {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub character: Option<Character>,
+    pub vehicle: Option<Vehicle>,
+    pub seat_index: i32,
+    pub fellow_passengers: Vec<Character>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogVehicleLeave {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub character: Option<Character>,
+    pub vehicle: Option<Vehicle>,
+    pub ride_distance: f32,
+    pub seat_index: i32,
+    pub max_speed: f32,
+    pub fellow_passengers: Vec<Character>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogVehicleDestroy {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub attack_id: i32,
+    pub attacker: Option<Character>,
+    pub vehicle: Option<Vehicle>,
+    pub damage_type_category: String,
+    pub damage_causer_name: String,
+    pub distance: f32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogWheelDestroy {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub attack_id: i32,
+    pub attacker: Option<Character>,
+    pub vehicle: Option<Vehicle>,
+    pub damage_type_category: String,
+    pub damage_causer_name: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogHeal {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub character: Option<Character>,
+    pub item: Option<Item>,
+    pub heal_amount: f32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// `LogItemPickup`, `LogItemUse`, `LogItemDrop` and `LogItemUnequip` only
+/// differ by `_T`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ItemEvent {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub character: Option<Character>,
+    pub item: Option<Item>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// `LogItemAttach` and `LogItemDetach`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ItemAttachmentEvent {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub character: Option<Character>,
+    pub parent_item: Option<Item>,
+    pub child_item: Option<Item>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogPlayerRevive {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub reviver: Option<Character>,
+    pub victim: Option<Character>,
+    #[serde(rename = "dBNOId")]
+    pub dbno_id: i32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// `LogPlayerUseThrowable` and `LogPlayerUseFlareGun`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogPlayerUseThrowable {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub attack_id: i32,
+    pub fire_weapon_stack_count: i32,
+    pub attacker: Option<Character>,
+    pub attack_type: String,
+    pub weapon: Option<Item>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogWeaponFireCount {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub character: Option<Character>,
+    pub weapon_id: String,
+    pub fire_count: i32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogObjectDestroy {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub character: Option<Character>,
+    pub object_type: String,
+    pub object_location: Location,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// `LogCarePackageSpawn` and `LogCarePackageLand`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CarePackageEvent {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub item_package: Option<ItemPackage>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// `LogVaultStart`, `LogSwimStart`, `LogSwimEnd` and `LogPlayerRedeploy`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CharacterEvent {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub character: Option<Character>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogRedZoneEnded {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub drivers: Vec<Character>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The `LogPlayerKill` of older telemetry, `LogPlayerKillV2` replaced it.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogPlayerKill {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub attack_id: i32,
+    pub killer: Option<Character>,
+    pub victim: Option<Character>,
+    pub assistant: Option<Character>,
+    #[serde(rename = "dBNOId")]
+    pub dbno_id: i32,
+    pub damage_reason: String,
+    pub damage_type_category: String,
+    pub damage_causer_name: String,
+    pub damage_causer_additional_info: Vec<String>,
+    pub victim_weapon: String,
+    pub victim_weapon_additional_info: Vec<String>,
+    pub distance: f32,
+    pub victim_game_result: Option<GameResult>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogVehicleDamage {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub attack_id: i32,
+    pub attacker: Option<Character>,
+    pub vehicle: Option<Vehicle>,
+    pub damage_type_category: String,
+    pub damage_causer_name: String,
+    pub damage: f32,
+    pub distance: f32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// `LogItemPutToVehicleTrunk` and `LogItemPickupFromVehicleTrunk`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VehicleTrunkEvent {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub character: Option<Character>,
+    pub vehicle: Option<Vehicle>,
+    pub item: Option<Item>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogCharacterCarry {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub character: Option<Character>,
+    pub carry_state: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogBlackZoneEnded {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub survivors: Vec<Character>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// An emergency pickup taking off with its riders.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogEmPickupLiftOff {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub instigator: Option<Character>,
+    pub riders: Vec<Character>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogPlayerDestroyBreachableWall {
+    #[serde(rename = "_D")]
+    pub date: String,
+    pub common: Common,
+    pub attacker: Option<Character>,
+    pub weapon: Option<Item>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// One element of the telemetry array, picked by `_T`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "_T")]
+pub enum TelemetryEvent {
+    // rikastuksen omat tapahtumat, _T palautetaan into_enrichment_event:ssä
+    LogPlayerTakeDamage(Event),
+    LogPlayerAttack(Event),
+    LogPlayerMakeGroggy(Event),
+    LogArmorDestroy(Event),
+    LogPlayerKillV2(Event),
+    LogMatchDefinition(Event),
+    LogPlayerCreate(Event),
+    LogItemEquip(Event),
+    LogItemPickupFromCarepackage(Event),
+    LogItemPickupFromLootbox(Event),
+
+    LogMatchStart(LogMatchStart),
+    LogMatchEnd(LogMatchEnd),
+    LogGameStatePeriodic(LogGameStatePeriodic),
+    LogPhaseChange(LogPhaseChange),
+    LogPlayerLogin(LogPlayerLogin),
+    LogPlayerLogout(LogPlayerLogout),
+    LogPlayerPosition(LogPlayerPosition),
+    LogParachuteLanding(LogParachuteLanding),
+    LogVehicleRide(LogVehicleRide),
+    LogVehicleLeave(LogVehicleLeave),
+    LogVehicleDestroy(LogVehicleDestroy),
+    LogWheelDestroy(LogWheelDestroy),
+    LogHeal(LogHeal),
+    LogItemPickup(ItemEvent),
+    LogItemUse(ItemEvent),
+    LogItemDrop(ItemEvent),
+    LogItemUnequip(ItemEvent),
+    LogItemAttach(ItemAttachmentEvent),
+    LogItemDetach(ItemAttachmentEvent),
+    LogPlayerRevive(LogPlayerRevive),
+    LogPlayerUseThrowable(LogPlayerUseThrowable),
+    LogWeaponFireCount(LogWeaponFireCount),
+    LogObjectDestroy(LogObjectDestroy),
+    LogCarePackageSpawn(CarePackageEvent),
+    LogCarePackageLand(CarePackageEvent),
+    LogVaultStart(CharacterEvent),
+    LogSwimStart(CharacterEvent),
+    LogSwimEnd(CharacterEvent),
+    LogRedZoneEnded(LogRedZoneEnded),
+    LogPlayerKill(LogPlayerKill),
+    LogVehicleDamage(LogVehicleDamage),
+    LogItemPutToVehicleTrunk(VehicleTrunkEvent),
+    LogItemPickupFromVehicleTrunk(VehicleTrunkEvent),
+    LogCharacterCarry(LogCharacterCarry),
+    LogPlayerRedeploy(CharacterEvent),
+    LogBlackZoneEnded(LogBlackZoneEnded),
+    LogEmPickupLiftOff(LogEmPickupLiftOff),
+    LogPlayerUseFlareGun(LogPlayerUseThrowable),
+    LogPlayerDestroyBreachableWall(LogPlayerDestroyBreachableWall),
+
+    /// A `_T` this model does not know yet, with all of its fields.
+    #[serde(skip)]
+    Other(UnknownEvent),
+    /// A known `_T` whose fields did not fit the model, e.g. a string where
+    /// a number was expected, with all of its fields.
+    #[serde(skip)]
+    Malformed(UnknownEvent),
+    /// Only seen while parsing, [`parse`] turns it into `Other`.
+    #[serde(other)]
+    Unknown,
+}
+
+/// An event of a type PUBG added after this model was written.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnknownEvent {
+    #[serde(rename = "_T")]
+    pub kind: String,
+    #[serde(flatten)]
+    pub fields: Map<String, Value>,
+}
+
+/// Parses one element of the telemetry array. Unknown types come back whole
+/// as [`TelemetryEvent::Other`] instead of being dropped, events that do not
+/// fit the model as [`TelemetryEvent::Malformed`] instead of failing the match.
+pub fn parse(value: Value) -> TelemetryEvent {
+    match TelemetryEvent::deserialize(&value) {
+        Ok(TelemetryEvent::Unknown) => TelemetryEvent::Other(untyped(value)),
+        Ok(event) => event,
+        Err(_) => TelemetryEvent::Malformed(untyped(value)),
+    }
+}
+
+// _T on jo luettu, kentät siirretään sellaisenaan ilman toista jäsennystä
+fn untyped(value: Value) -> UnknownEvent {
+    let mut fields = match value {
+        Value::Object(fields) => fields,
+        _ => Map::new(),
+    };
+    let kind = match fields.remove("_T") {
+        Some(Value::String(kind)) => kind,
+        _ => String::new(),
+    };
+    UnknownEvent { kind, fields }
+}
+
+/// Collects the `extra` keys of a typed object and of the objects in it.
+trait UnknownFields {
+    fn unknown_fields(&self, path: &mut Vec<&'static str>, fields: &mut BTreeSet<String>);
+}
+
+impl<T: UnknownFields> UnknownFields for Option<T> {
+    fn unknown_fields(&self, path: &mut Vec<&'static str>, fields: &mut BTreeSet<String>) {
+        if let Some(object) = self {
+            object.unknown_fields(path, fields);
+        }
+    }
+}
+
+impl<T: UnknownFields> UnknownFields for Vec<T> {
+    fn unknown_fields(&self, path: &mut Vec<&'static str>, fields: &mut BTreeSet<String>) {
+        for object in self {
+            object.unknown_fields(path, fields);
+        }
+    }
+}
+
+/// `LogVehicleRide.fellowPassengers.isNew` for the key `isNew` of a fellow
+/// passenger, the path is built only when there is something to report.
+fn field_path(path: &[&str], key: &str) -> String {
+    let mut segments: Vec<String> = Vec::with_capacity(path.len() + 1);
+    for (index, segment) in path.iter().enumerate() {
+        if index == 0 {
+            segments.push(segment.to_string());
+            continue;
+        }
+        // kenttien nimet ovat rust-muodossa, raporttiin JSON:n camelCase
+        let mut parts = segment.split('_');
+        let mut camel = parts.next().unwrap_or_default().to_string();
+        for part in parts {
+            let mut chars = part.chars();
+            if let Some(first) = chars.next() {
+                camel.extend(first.to_uppercase());
+                camel.push_str(chars.as_str());
+            }
+        }
+        segments.push(camel);
+    }
+    segments.push(key.to_string());
+    segments.join(".")
+}
+
+macro_rules! unknown_fields {
+    ($($model:ident { $($nested:ident),* })*) => {
+        $(impl UnknownFields for $model {
+            fn unknown_fields(&self, path: &mut Vec<&'static str>, fields: &mut BTreeSet<String>) {
+                fields.extend(self.extra.keys().map(|key| field_path(path, key)));
+                $(
+                    path.push(stringify!($nested));
+                    self.$nested.unknown_fields(path, fields);
+                    path.pop();
+                )*
+            }
+        })*
+    };
+}
+
+unknown_fields! {
+    Common {}
+    Character {}
+    CharacterWrapper { character }
+    Item {}
+    ItemPackage { items }
+    Vehicle {}
+    GameState {}
+    GameResult {}
+    GameResultOnFinished { results }
+    LogMatchStart { common, characters }
+    LogMatchEnd { common, characters, game_result_on_finished }
+    LogGameStatePeriodic { common, game_state }
+    LogPhaseChange { common }
+    LogPlayerPosition { common, character, vehicle }
+    LogPlayerLogin { common }
+    LogPlayerLogout { common }
+    LogParachuteLanding { common, character }
+    LogVehicleRide { common, character, vehicle, fellow_passengers }
+    LogVehicleLeave { common, character, vehicle, fellow_passengers }
+    LogVehicleDestroy { common, attacker, vehicle }
+    LogWheelDestroy { common, attacker, vehicle }
+    LogHeal { common, character, item }
+    ItemEvent { common, character, item }
+    ItemAttachmentEvent { common, character, parent_item, child_item }
+    LogPlayerRevive { common, reviver, victim }
+    LogPlayerUseThrowable { common, attacker, weapon }
+    LogWeaponFireCount { common, character }
+    LogObjectDestroy { common, character }
+    CarePackageEvent { common, item_package }
+    CharacterEvent { common, character }
+    LogRedZoneEnded { common, drivers }
+    LogPlayerKill { common, killer, victim, assistant, victim_game_result }
+    LogVehicleDamage { common, attacker, vehicle }
+    VehicleTrunkEvent { common, character, vehicle, item }
+    LogCharacterCarry { common, character }
+    LogBlackZoneEnded { common, survivors }
+    LogEmPickupLiftOff { common, instigator, riders }
+    LogPlayerDestroyBreachableWall { common, attacker, weapon }
+}
+
+impl TelemetryEvent {
+    /// Fields of the event the typed model does not know, as
+    /// `_T.path.field`. The enrichment's own events keep every field in
+    /// their `extra` on purpose, so they are left out.
+    pub fn unknown_fields(&self) -> BTreeSet<String> {
+        let (kind, model): (&'static str, &dyn UnknownFields) = match self {
+            TelemetryEvent::LogMatchStart(e) => ("LogMatchStart", e),
+            TelemetryEvent::LogMatchEnd(e) => ("LogMatchEnd", e),
+            TelemetryEvent::LogGameStatePeriodic(e) => ("LogGameStatePeriodic", e),
+            TelemetryEvent::LogPhaseChange(e) => ("LogPhaseChange", e),
+            TelemetryEvent::LogPlayerLogin(e) => ("LogPlayerLogin", e),
+            TelemetryEvent::LogPlayerLogout(e) => ("LogPlayerLogout", e),
+            TelemetryEvent::LogPlayerPosition(e) => ("LogPlayerPosition", e),
+            TelemetryEvent::LogParachuteLanding(e) => ("LogParachuteLanding", e),
+            TelemetryEvent::LogVehicleRide(e) => ("LogVehicleRide", e),
+            TelemetryEvent::LogVehicleLeave(e) => ("LogVehicleLeave", e),
+            TelemetryEvent::LogVehicleDestroy(e) => ("LogVehicleDestroy", e),
+            TelemetryEvent::LogWheelDestroy(e) => ("LogWheelDestroy", e),
+            TelemetryEvent::LogHeal(e) => ("LogHeal", e),
+            TelemetryEvent::LogItemPickup(e) => ("LogItemPickup", e),
+            TelemetryEvent::LogItemUse(e) => ("LogItemUse", e),
+            TelemetryEvent::LogItemDrop(e) => ("LogItemDrop", e),
+            TelemetryEvent::LogItemUnequip(e) => ("LogItemUnequip", e),
+            TelemetryEvent::LogItemAttach(e) => ("LogItemAttach", e),
+            TelemetryEvent::LogItemDetach(e) => ("LogItemDetach", e),
+            TelemetryEvent::LogPlayerRevive(e) => ("LogPlayerRevive", e),
+            TelemetryEvent::LogPlayerUseThrowable(e) => ("LogPlayerUseThrowable", e),
+            TelemetryEvent::LogWeaponFireCount(e) => ("LogWeaponFireCount", e),
+            TelemetryEvent::LogObjectDestroy(e) => ("LogObjectDestroy", e),
+            TelemetryEvent::LogCarePackageSpawn(e) => ("LogCarePackageSpawn", e),
+            TelemetryEvent::LogCarePackageLand(e) => ("LogCarePackageLand", e),
+            TelemetryEvent::LogVaultStart(e) => ("LogVaultStart", e),
+            TelemetryEvent::LogSwimStart(e) => ("LogSwimStart", e),
+            TelemetryEvent::LogSwimEnd(e) => ("LogSwimEnd", e),
+            TelemetryEvent::LogRedZoneEnded(e) => ("LogRedZoneEnded", e),
+            TelemetryEvent::LogPlayerKill(e) => ("LogPlayerKill", e),
+            TelemetryEvent::LogVehicleDamage(e) => ("LogVehicleDamage", e),
+            TelemetryEvent::LogItemPutToVehicleTrunk(e) => ("LogItemPutToVehicleTrunk", e),
+            TelemetryEvent::LogItemPickupFromVehicleTrunk(e) => ("LogItemPickupFromVehicleTrunk", e),
+            TelemetryEvent::LogCharacterCarry(e) => ("LogCharacterCarry", e),
+            TelemetryEvent::LogPlayerRedeploy(e) => ("LogPlayerRedeploy", e),
+            TelemetryEvent::LogBlackZoneEnded(e) => ("LogBlackZoneEnded", e),
+            TelemetryEvent::LogEmPickupLiftOff(e) => ("LogEmPickupLiftOff", e),
+            TelemetryEvent::LogPlayerUseFlareGun(e) => ("LogPlayerUseFlareGun", e),
+            TelemetryEvent::LogPlayerDestroyBreachableWall(e) => ("LogPlayerDestroyBreachableWall", e),
+            _ => return BTreeSet::new(),
+        };
+        let mut fields = BTreeSet::new();
+        model.unknown_fields(&mut vec![kind], &mut fields);
+        fields
+    }
+
+    /// The event as the enrichment's flat [`Event`], for the ten types it
+    /// handles. Picking the variant consumes `_T`, so it is put back here.
+    pub fn into_enrichment_event(self) -> Option<Event> {
+        let (action, mut event) = match self {
+            TelemetryEvent::LogPlayerTakeDamage(event) => (ActionType::LogPlayerTakeDamage, event),
+            TelemetryEvent::LogPlayerAttack(event) => (ActionType::LogPlayerAttack, event),
+            TelemetryEvent::LogPlayerMakeGroggy(event) => (ActionType::LogPlayerMakeGroggy, event),
+            TelemetryEvent::LogArmorDestroy(event) => (ActionType::LogArmorDestroy, event),
+            TelemetryEvent::LogPlayerKillV2(event) => (ActionType::LogPlayerKillV2, event),
+            TelemetryEvent::LogMatchDefinition(event) => (ActionType::LogMatchDefinition, event),
+            TelemetryEvent::LogPlayerCreate(event) => (ActionType::LogPlayerCreate, event),
+            TelemetryEvent::LogItemEquip(event) => (ActionType::LogItemEquip, event),
+            TelemetryEvent::LogItemPickupFromCarepackage(event) => (ActionType::LogItemPickupFromCarepackage, event),
+            TelemetryEvent::LogItemPickupFromLootbox(event) => (ActionType::LogItemPickupFromLootbox, event),
+            _ => return None,
+        };
+        event.action = Some(action);
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keeps_unknown_types_whole() {
+        let event = parse(json!({"_T": "LogSomethingNew", "_D": "2025-05-01T18:00:00Z", "value": 3}));
+        let TelemetryEvent::Other(unknown) = event else {
+            panic!("expected an unknown event, got {:?}", event);
+        };
+        assert_eq!(unknown.kind, "LogSomethingNew");
+        assert_eq!(unknown.fields.get("value"), Some(&json!(3)));
+        assert!(!unknown.fields.contains_key("_T"));
+    }
+
+    #[test]
+    fn reports_unknown_fields_of_known_types() {
+        let event = parse(json!({
+            "_T": "LogVehicleRide",
+            "_D": "2025-05-01T18:00:00Z",
+            "character": {"name": "a", "accountId": "account.a", "isInShelter": true},
+            "vehicle": {"vehicleType": "WheeledVehicle", "boostLevel": 2},
+            "fellowPassengers": [{"name": "b", "isInShelter": false}],
+            "seatIndex": 0,
+            "rideMood": "calm",
+        }));
+        let fields: Vec<String> = event.unknown_fields().into_iter().collect();
+        assert_eq!(
+            fields,
+            [
+                "LogVehicleRide.character.isInShelter",
+                "LogVehicleRide.fellowPassengers.isInShelter",
+                "LogVehicleRide.rideMood",
+                "LogVehicleRide.vehicle.boostLevel",
+            ]
+        );
+    }
+
+    #[test]
+    fn models_the_rarer_documented_types() {
+        let kinds = [
+            "LogPlayerKill",
+            "LogVehicleDamage",
+            "LogItemPutToVehicleTrunk",
+            "LogItemPickupFromVehicleTrunk",
+            "LogCharacterCarry",
+            "LogPlayerRedeploy",
+            "LogBlackZoneEnded",
+            "LogEmPickupLiftOff",
+            "LogPlayerUseFlareGun",
+            "LogPlayerDestroyBreachableWall",
+        ];
+        for kind in kinds {
+            let event = parse(json!({"_T": kind, "_D": "2025-05-01T18:00:00Z", "common": {"isGame": 1.0}}));
+            assert!(!matches!(event, TelemetryEvent::Other(_) | TelemetryEvent::Malformed(_)), "{} is not modeled", kind);
+            assert!(event.unknown_fields().is_empty());
+        }
+
+        let event = parse(json!({
+            "_T": "LogPlayerKill",
+            "_D": "2025-05-01T18:00:00Z",
+            "killer": {"name": "a", "accountId": "account.a"},
+            "victim": {"name": "b", "accountId": "account.b"},
+            "dBNOId": 3,
+            "damageCauserAdditionalInfo": ["Item_Attach_Weapon_Upper_ACOG_01_C"],
+            "distance": 4200.0,
+        }));
+        let TelemetryEvent::LogPlayerKill(kill) = &event else {
+            panic!("expected LogPlayerKill, got {:?}", event);
+        };
+        assert_eq!(kill.victim.as_ref().unwrap().account_id, "account.b");
+        assert_eq!(kill.dbno_id, 3);
+        assert!(event.unknown_fields().is_empty());
+    }
+
+    #[test]
+    fn accepts_null_objects() {
+        let event = parse(json!({"_T": "LogVehicleLeave", "_D": "2025-05-01T18:00:00Z", "character": null, "vehicle": null}));
+        let TelemetryEvent::LogVehicleLeave(leave) = event else {
+            panic!("expected LogVehicleLeave, got {:?}", event);
+        };
+        assert!(leave.character.is_none() && leave.vehicle.is_none());
+    }
+
+    #[test]
+    fn keeps_events_that_do_not_fit_the_model() {
+        let event = parse(json!({"_T": "LogHeal", "_D": "2025-05-01T18:00:00Z", "healAmount": "a lot"}));
+        let TelemetryEvent::Malformed(malformed) = event else {
+            panic!("expected a malformed event, got {:?}", event);
+        };
+        assert_eq!(malformed.kind, "LogHeal");
+        assert_eq!(malformed.fields.get("healAmount"), Some(&json!("a lot")));
+    }
+}
@@ -0,0 +1,443 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::error::FetchError;
+use crate::identity::TrackedPlayers;
+use crate::telemetry_events::{GameState, TelemetryEvent};
+use crate::write_atomic;
+use crate::{Event, Location};
+use shared_utils::paths::data_path;
+use shared_utils::redis_loader::load_match_zones_to_redis;
+
+const BLUE_ZONE_DAMAGE: &str = "Damage_BlueZone";
+const AIRCRAFT: &str = "TransportAircraft";
+
+/// A zone on the map, in centimeters like the positions.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+}
+
+impl Circle {
+    // säde on nolla kun aluetta ei vielä (tai enää) ole
+    fn new(position: &Location, radius: f32) -> Option<Circle> {
+        (radius > 0.0).then_some(Circle { x: position.x, y: position.y, radius })
+    }
+
+    fn contains(&self, location: &Location) -> bool {
+        let dx = location.x - self.x;
+        let dy = location.y - self.y;
+        dx * dx + dy * dy <= self.radius * self.radius
+    }
+}
+
+/// The zones of one `LogGameStatePeriodic`. `safe_zone` is the closing blue
+/// circle, players outside it take blue zone damage. `poison_gas` is the
+/// white circle the blue one is closing to.
+#[derive(Serialize, Debug, Clone)]
+pub struct ZoneSample {
+    /// Seconds since `LogMatchDefinition`.
+    pub time: f32,
+    pub safe_zone: Option<Circle>,
+    pub poison_gas: Option<Circle>,
+    pub red_zone: Option<Circle>,
+}
+
+/// From one white circle to the next.
+#[derive(Serialize, Debug, Clone)]
+pub struct ZonePhase {
+    pub phase: usize,
+    pub start: f32,
+    pub end: f32,
+    /// Blue circle when the phase started.
+    pub safe_zone: Option<Circle>,
+    pub poison_gas: Circle,
+    pub red_zones: Vec<Circle>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlayerPhase {
+    pub phase: usize,
+    /// Seconds from the start of the phase until the player was inside the
+    /// white circle, 0 if they already were, `None` if they never got in.
+    pub late_by: Option<f32>,
+    pub outside_safe_zone: f32,
+    pub blue_zone_damage: f32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlayerZones {
+    pub name: String,
+    pub account_id: String,
+    pub team_id: i32,
+    /// Seconds outside the blue circle over the whole match.
+    pub outside_safe_zone: f32,
+    pub blue_zone_damage: f32,
+    /// Phases the player was alive in, by positions or blue zone damage.
+    pub phases: Vec<PlayerPhase>,
+}
+
+/// `data/zones/{id}.json`, the zone timeline and how the tracked players
+/// played it.
+#[derive(Serialize, Debug)]
+pub struct MatchZones {
+    pub match_id: String,
+    pub map_name: String,
+    pub timeline: Vec<ZoneSample>,
+    pub phases: Vec<ZonePhase>,
+    pub players: Vec<PlayerZones>,
+}
+
+struct ZoneState {
+    time: DateTime<FixedOffset>,
+    safe_zone: Option<Circle>,
+    poison_gas: Option<Circle>,
+    red_zone: Option<Circle>,
+}
+
+impl ZoneState {
+    fn new(time: DateTime<FixedOffset>, state: &GameState) -> Self {
+        ZoneState {
+            time,
+            safe_zone: Circle::new(&state.safety_zone_position, state.safety_zone_radius),
+            poison_gas: Circle::new(&state.poison_gas_warning_position, state.poison_gas_warning_radius),
+            red_zone: Circle::new(&state.red_zone_position, state.red_zone_radius),
+        }
+    }
+}
+
+struct PositionTrack {
+    name: String,
+    team_id: i32,
+    samples: Vec<(DateTime<FixedOffset>, Location)>,
+}
+
+/// Zone states, and the positions and blue zone damage of the tracked
+/// players, collected from the telemetry.
+pub struct Zones {
+    tracked: TrackedPlayers,
+    states: Vec<ZoneState>,
+    positions: HashMap<String, PositionTrack>,
+    blue_zone_damage: Vec<(String, DateTime<FixedOffset>, f32)>,
+}
+
+impl Zones {
+    pub fn new(tracked: TrackedPlayers) -> Self {
+        Zones { tracked, states: Vec::new(), positions: HashMap::new(), blue_zone_damage: Vec::new() }
+    }
+
+    pub fn visit(&mut self, event: &TelemetryEvent) {
+        match event {
+            TelemetryEvent::LogGameStatePeriodic(e) => {
+                let (Some(game_state), Ok(time)) = (&e.game_state, DateTime::parse_from_rfc3339(&e.date)) else {
+                    return;
+                };
+                self.states.push(ZoneState::new(time, game_state));
+            }
+            TelemetryEvent::LogPlayerPosition(e) => {
+                // koneessa ei olla vielä pelialueella
+                if e.vehicle.as_ref().is_some_and(|vehicle| vehicle.vehicle_type == AIRCRAFT) {
+                    return;
+                }
+                let (Some(character), Ok(time)) = (&e.character, DateTime::parse_from_rfc3339(&e.date)) else {
+                    return;
+                };
+                if !self.tracked.contains(&character.account_id, &character.name) {
+                    return;
+                }
+                let track = self.positions.entry(character.account_id.clone()).or_insert_with(|| PositionTrack {
+                    name: character.name.clone(),
+                    team_id: character.team_id,
+                    samples: Vec::new(),
+                });
+                track.samples.push((time, character.location.clone()));
+            }
+            TelemetryEvent::LogPlayerTakeDamage(e) => self.observe_damage(e),
+            _ => {}
+        }
+    }
+
+    fn observe_damage(&mut self, event: &Event) {
+        if event.damage_type_category.as_deref() != Some(BLUE_ZONE_DAMAGE) {
+            return;
+        }
+        let (Some(victim), Some(date)) = (&event.victim, &event.event_time) else {
+            return;
+        };
+        if !self.tracked.contains(&victim.account_id, &victim.name) {
+            return;
+        }
+        if let Ok(time) = DateTime::parse_from_rfc3339(date) {
+            self.blue_zone_damage.push((victim.account_id.clone(), time, event.damage.unwrap_or(0.0)));
+        }
+    }
+
+    /// Zone timeline of the match and the zone play of every tracked player.
+    pub fn analyze(mut self, match_id: &str, map_name: &str, match_start_time: &str) -> MatchZones {
+        self.states.sort_by_key(|state| state.time);
+        let start = DateTime::parse_from_rfc3339(match_start_time).ok().or_else(|| self.states.first().map(|state| state.time));
+        let seconds = |time: DateTime<FixedOffset>| start.map(|start| (time - start).num_milliseconds() as f32 / 1000.0).unwrap_or(0.0);
+
+        let timeline: Vec<ZoneSample> = self
+            .states
+            .iter()
+            .map(|state| ZoneSample { time: seconds(state.time), safe_zone: state.safe_zone, poison_gas: state.poison_gas, red_zone: state.red_zone })
+            .collect();
+        let phases = phases(&self.states, &timeline);
+
+        let mut players: Vec<PlayerZones> = self
+            .positions
+            .iter()
+            .map(|(account_id, track)| {
+                let mut samples: Vec<(f32, &Location)> = track.samples.iter().map(|(time, location)| (seconds(*time), location)).collect();
+                samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+                let damage: Vec<(f32, f32)> = self
+                    .blue_zone_damage
+                    .iter()
+                    .filter(|(victim, _, _)| victim == account_id)
+                    .map(|(_, time, damage)| (seconds(*time), *damage))
+                    .collect();
+                player_zones(account_id, track, &self.tracked, &samples, &damage, &timeline, &phases)
+            })
+            .collect();
+        players.sort_by(|a, b| a.team_id.cmp(&b.team_id).then_with(|| a.name.cmp(&b.name)));
+
+        MatchZones { match_id: match_id.to_string(), map_name: map_name.to_string(), timeline, phases, players }
+    }
+}
+
+/// A new phase starts whenever a new white circle appears.
+fn phases(states: &[ZoneState], timeline: &[ZoneSample]) -> Vec<ZonePhase> {
+    let mut phases: Vec<ZonePhase> = Vec::new();
+    for (state, sample) in states.iter().zip(timeline) {
+        if let Some(poison_gas) = state.poison_gas {
+            if phases.last().is_none_or(|phase| phase.poison_gas != poison_gas) {
+                if let Some(previous) = phases.last_mut() {
+                    previous.end = sample.time;
+                }
+                phases.push(ZonePhase {
+                    phase: phases.len() + 1,
+                    start: sample.time,
+                    end: sample.time,
+                    safe_zone: state.safe_zone,
+                    poison_gas,
+                    red_zones: Vec::new(),
+                });
+            }
+        }
+        if let Some(phase) = phases.last_mut() {
+            phase.end = sample.time;
+            if let Some(red_zone) = state.red_zone {
+                if !phase.red_zones.contains(&red_zone) {
+                    phase.red_zones.push(red_zone);
+                }
+            }
+        }
+    }
+    phases
+}
+
+fn phase_at(phases: &[ZonePhase], time: f32) -> Option<usize> {
+    phases.iter().rposition(|phase| phase.start <= time)
+}
+
+fn safe_zone_at(timeline: &[ZoneSample], time: f32) -> Option<Circle> {
+    timeline.iter().rev().find(|sample| sample.time <= time).and_then(|sample| sample.safe_zone)
+}
+
+fn player_zones(
+    account_id: &str,
+    track: &PositionTrack,
+    tracked: &TrackedPlayers,
+    samples: &[(f32, &Location)],
+    damage: &[(f32, f32)],
+    timeline: &[ZoneSample],
+    phases: &[ZonePhase],
+) -> PlayerZones {
+    let mut outside = vec![0.0; phases.len()];
+    let mut outside_total = 0.0;
+    // välin aika lasketaan sen alun sijainnin mukaan, sijainti tulee noin 10 s välein
+    for pair in samples.windows(2) {
+        let (time, location) = pair[0];
+        if safe_zone_at(timeline, time).is_some_and(|safe_zone| !safe_zone.contains(location)) {
+            let step = pair[1].0 - time;
+            outside_total += step;
+            if let Some(index) = phase_at(phases, time) {
+                outside[index] += step;
+            }
+        }
+    }
+
+    let mut damage_by_phase = vec![0.0; phases.len()];
+    for (time, amount) in damage {
+        if let Some(index) = phase_at(phases, *time) {
+            damage_by_phase[index] += amount;
+        }
+    }
+
+    let mut player_phases: Vec<PlayerPhase> = Vec::new();
+    for (index, phase) in phases.iter().enumerate() {
+        let during = |time: f32| time >= phase.start && time <= phase.end;
+        let alive = samples.iter().any(|(time, _)| during(*time)) || damage.iter().any(|(time, _)| during(*time));
+        if !alive {
+            continue;
+        }
+        // viimeinen sijainti ennen vaihetta kertoo oliko pelaaja jo valmiiksi sisällä,
+        // kesken vaiheen laskeutunut myöhästyy vasta maassa
+        let (from, since) = match samples.iter().rposition(|(time, _)| *time <= phase.start) {
+            Some(from) => (from, phase.start),
+            None => (0, samples.first().map_or(phase.start, |(time, _)| *time)),
+        };
+        let late_by = samples[from..]
+            .iter()
+            .take_while(|(time, _)| *time <= phase.end)
+            .find(|(_, location)| phase.poison_gas.contains(location))
+            .map(|(time, _)| (time - since).max(0.0));
+        player_phases.push(PlayerPhase {
+            phase: phase.phase,
+            late_by,
+            outside_safe_zone: outside[index],
+            blue_zone_damage: damage_by_phase[index],
+        });
+    }
+
+    PlayerZones {
+        name: tracked.current_name(account_id).unwrap_or(&track.name).to_string(),
+        account_id: account_id.to_string(),
+        team_id: track.team_id,
+        outside_safe_zone: outside_total,
+        blue_zone_damage: damage.iter().fold(0.0, |total, (_, amount)| total + amount),
+        phases: player_phases,
+    }
+}
+
+/// Writes `data/zones/{id}.json` and loads it into Redis.
+pub fn save(zones: &MatchZones) -> Result<(), FetchError> {
+    let json_data = serde_json::to_string_pretty(zones).map_err(|e| FetchError::json(format!("zones of match {}", zones.match_id), e))?;
+    write_atomic(&data_path(&format!("zones/{}.json", zones.match_id)), json_data.as_bytes())?;
+
+    if let Err(e) = load_match_zones_to_redis(&zones.match_id) {
+        eprintln!("Failed to load zones of match {} into Redis: {}", zones.match_id, e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry_events::parse;
+    use chrono::Duration;
+    use serde_json::{json, Value};
+
+    const START: &str = "2025-05-01T18:00:00Z";
+
+    fn at(seconds: i64) -> String {
+        (DateTime::parse_from_rfc3339(START).unwrap() + Duration::seconds(seconds)).to_rfc3339()
+    }
+
+    fn character(account_id: &str, x: f32) -> Value {
+        json!({
+            "name": account_id, "accountId": account_id, "teamId": 1, "health": 100.0, "ranking": 0, "individualRanking": 0,
+            "isInVehicle": false, "location": {"x": x, "y": 0.0, "z": 0.0}
+        })
+    }
+
+    fn event(kind: &str, seconds: i64, fields: Value) -> TelemetryEvent {
+        let mut value = json!({"_T": kind, "_D": at(seconds), "common": {"isGame": 1.0}});
+        value.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+        parse(value)
+    }
+
+    // alueiden keskipiste on origossa, vain säteet muuttuvat
+    fn state(seconds: i64, safe_zone: f32, poison_gas: f32) -> TelemetryEvent {
+        let origin = json!({"x": 0.0, "y": 0.0, "z": 0.0});
+        let game_state = json!({
+            "safetyZonePosition": origin, "safetyZoneRadius": safe_zone,
+            "poisonGasWarningPosition": origin, "poisonGasWarningRadius": poison_gas,
+        });
+        event("LogGameStatePeriodic", seconds, json!({"gameState": game_state}))
+    }
+
+    fn position(seconds: i64, account_id: &str, x: f32) -> TelemetryEvent {
+        event("LogPlayerPosition", seconds, json!({"character": character(account_id, x)}))
+    }
+
+    fn blue_zone(seconds: i64, account_id: &str, damage: f32) -> TelemetryEvent {
+        let location = json!({"x": 0.0, "y": 0.0, "z": 0.0});
+        event(
+            "LogPlayerTakeDamage",
+            seconds,
+            json!({"victim": character(account_id, 0.0), "damage": damage, "damageTypeCategory": BLUE_ZONE_DAMAGE, "location": location}),
+        )
+    }
+
+    fn analyze(events: Vec<TelemetryEvent>) -> MatchZones {
+        let mut zones = Zones::new(TrackedPlayers::of(&["a"]));
+        for event in &events {
+            zones.visit(event);
+        }
+        zones.analyze("match", "Erangel", START)
+    }
+
+    // kaksi vaihetta, valkoinen kehä pienenee 120 s kohdalla 500 m:stä 200 m:iin.
+    fn two_phases(mut events: Vec<TelemetryEvent>) -> MatchZones {
+        events.extend([state(0, 100000.0, 50000.0), state(60, 80000.0, 50000.0), state(120, 50000.0, 20000.0), state(180, 40000.0, 20000.0)]);
+        analyze(events)
+    }
+
+    #[test]
+    fn splits_phases_on_a_new_white_circle() {
+        let zones = two_phases(Vec::new());
+        assert_eq!(zones.timeline.len(), 4);
+        let spans: Vec<(usize, f32, f32)> = zones.phases.iter().map(|phase| (phase.phase, phase.start, phase.end)).collect();
+        assert_eq!(spans, [(1, 0.0, 120.0), (2, 120.0, 180.0)]);
+        assert_eq!(zones.phases[1].safe_zone.unwrap().radius, 50000.0);
+        assert_eq!(zones.phases[1].poison_gas.radius, 20000.0);
+    }
+
+    #[test]
+    fn measures_time_outside_and_lateness_per_phase() {
+        let zones = two_phases(vec![
+            position(0, "a", 90000.0),
+            position(30, "a", 60000.0),
+            // sininen kehä on 800 m, 30 s ulkona
+            position(60, "a", 90000.0),
+            position(90, "a", 40000.0),
+            position(120, "a", 40000.0),
+            // sininen kehä on 500 m, 30 s ulkona
+            position(150, "a", 60000.0),
+            position(180, "a", 10000.0),
+            position(60, "b", 90000.0),
+        ]);
+        assert_eq!(zones.players.len(), 1);
+        let player = &zones.players[0];
+        assert_eq!(player.outside_safe_zone, 60.0);
+        let phases: Vec<(usize, Option<f32>, f32)> = player.phases.iter().map(|phase| (phase.phase, phase.late_by, phase.outside_safe_zone)).collect();
+        assert_eq!(phases, [(1, Some(90.0), 30.0), (2, Some(60.0), 30.0)]);
+    }
+
+    #[test]
+    fn never_reaching_the_white_circle_is_not_late_but_missing() {
+        let zones = two_phases(vec![position(0, "a", 10000.0), position(120, "a", 30000.0), position(180, "a", 30000.0)]);
+        let late_by: Vec<Option<f32>> = zones.players[0].phases.iter().map(|phase| phase.late_by).collect();
+        assert_eq!(late_by, [Some(0.0), None]);
+    }
+
+    #[test]
+    fn adds_blue_zone_damage_to_the_phase() {
+        let zones = two_phases(vec![
+            position(0, "a", 90000.0),
+            position(180, "a", 90000.0),
+            blue_zone(70, "a", 5.0),
+            blue_zone(160, "a", 7.0),
+            blue_zone(160, "b", 9.0),
+        ]);
+        let player = &zones.players[0];
+        assert_eq!(player.blue_zone_damage, 12.0);
+        let damage: Vec<f32> = player.phases.iter().map(|phase| phase.blue_zone_damage).collect();
+        assert_eq!(damage, [5.0, 7.0]);
+    }
+}
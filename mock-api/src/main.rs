@@ -0,0 +1,302 @@
+//! Local stand-in for `api.pubg.com`, serving the files in `fixtures/`.
+//!
+//! Run it from the repository root and point the fetcher at it:
+//!
+//! ```text
+//! cargo run -p mock-api
+//! api_key=mock cargo run -p api-fetch -- --api-base-url http://127.0.0.1:8080 poll
+//! ```
+//!
+//! `/players`, `/seasons`, ranked stats and mastery answer with
+//! `X-RateLimit-*` headers and 429s once `MOCK_RATE_LIMIT` requests per minute
+//! (default 10, 0 disables) are used up, like a default API key. Matches and
+//! telemetry are not limited.
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use warp::http::StatusCode;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+#[derive(Clone)]
+struct Config {
+    fixtures: PathBuf,
+    public_url: String,
+}
+
+struct RateWindow {
+    limit: u32,
+    started: u64,
+    used: u32,
+}
+
+type RateLimiter = Arc<Mutex<RateWindow>>;
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn read_fixture(config: &Config, path: &str) -> Option<Value> {
+    let contents = fs::read_to_string(config.fixtures.join(path)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn json_response(status: StatusCode, value: &Value) -> Response {
+    warp::reply::with_status(warp::reply::json(value), status).into_response()
+}
+
+fn not_found(detail: String) -> Response {
+    json_response(StatusCode::NOT_FOUND, &json!({"errors": [{"title": "Not Found", "detail": detail}]}))
+}
+
+/// Counts the request against the current one minute window and adds the
+/// rate limit headers, or answers 429 if the window is used up.
+fn rate_limited(limiter: &RateLimiter, reply: impl FnOnce() -> Response) -> Response {
+    let mut window = limiter.lock().unwrap();
+    if window.limit == 0 {
+        return reply();
+    }
+
+    let now = now_secs();
+    if now >= window.started + 60 {
+        window.started = now;
+        window.used = 0;
+    }
+    let reset = window.started + 60;
+
+    let mut response = if window.used >= window.limit {
+        json_response(StatusCode::TOO_MANY_REQUESTS, &json!({"errors": [{"title": "Too Many Requests"}]}))
+    } else {
+        window.used += 1;
+        reply()
+    };
+
+    let headers = response.headers_mut();
+    headers.insert("X-RateLimit-Limit", window.limit.into());
+    headers.insert("X-RateLimit-Remaining", (window.limit - window.used).into());
+    headers.insert("X-RateLimit-Reset", reset.into());
+    response
+}
+
+fn filter_values(query: &HashMap<String, String>, key: &str) -> Vec<String> {
+    query
+        .get(key)
+        .map(|value| value.split(',').map(|part| part.trim().to_string()).filter(|part| !part.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+fn players(config: &Config, shard: String, query: HashMap<String, String>) -> Response {
+    let names = filter_values(&query, "filter[playerNames]");
+    let ids = filter_values(&query, "filter[playerIds]");
+    let Some(fixture) = read_fixture(config, "players.json") else {
+        return not_found("fixtures/players.json missing".to_string());
+    };
+
+    let found: Vec<Value> = fixture["data"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|player| player["attributes"]["shardId"].as_str().unwrap_or("steam") == shard)
+        .filter(|player| {
+            let name = player["attributes"]["name"].as_str().unwrap_or_default();
+            let id = player["id"].as_str().unwrap_or_default();
+            names.iter().any(|n| n == name) || ids.iter().any(|i| i == id)
+        })
+        .collect();
+
+    if found.is_empty() {
+        return not_found(format!("no players matching {:?}{:?} on {}", names, ids, shard));
+    }
+    json_response(StatusCode::OK, &json!({"data": found, "links": {}, "meta": {}}))
+}
+
+fn match_data(config: &Config, match_id: String) -> Response {
+    let Some(mut fixture) = read_fixture(config, &format!("matches/{}.json", match_id)) else {
+        return not_found(format!("match {} not in fixtures", match_id));
+    };
+
+    // telemetria osoite ohjataan tähän palvelimeen CDN:n sijaan
+    if let Some(included) = fixture["included"].as_array_mut() {
+        for item in included.iter_mut().filter(|item| item["type"] == "asset") {
+            item["attributes"]["URL"] = json!(format!("{}/telemetry/{}", config.public_url, match_id));
+        }
+    }
+    json_response(StatusCode::OK, &fixture)
+}
+
+fn telemetry(config: &Config, match_id: String) -> Response {
+    let plain = config.fixtures.join(format!("matches/{}.telemetry.json", match_id));
+    let gzipped = config.fixtures.join(format!("matches/{}.telemetry.json.gz", match_id));
+
+    if let Ok(bytes) = fs::read(&gzipped) {
+        let mut response = Response::new(bytes.into());
+        response.headers_mut().insert("Content-Type", "application/json".parse().unwrap());
+        response.headers_mut().insert("Content-Encoding", "gzip".parse().unwrap());
+        return response;
+    }
+    match fs::read(&plain) {
+        Ok(bytes) => {
+            let mut response = Response::new(bytes.into());
+            response.headers_mut().insert("Content-Type", "application/json".parse().unwrap());
+            response
+        }
+        Err(_) => not_found(format!("no telemetry for match {}", match_id)),
+    }
+}
+
+fn seasons(config: &Config) -> Response {
+    match read_fixture(config, "seasons.json") {
+        Some(fixture) => json_response(StatusCode::OK, &fixture),
+        None => not_found("fixtures/seasons.json missing".to_string()),
+    }
+}
+
+fn season_stats(config: &Config, season_id: String, game_mode: String, query: HashMap<String, String>) -> Response {
+    let ids = filter_values(&query, "filter[playerIds]");
+    let Some(fixture) = read_fixture(config, "season_stats.json") else {
+        return not_found("fixtures/season_stats.json missing".to_string());
+    };
+
+    let data: Vec<Value> = ids
+        .iter()
+        .filter_map(|id| {
+            let stats = fixture.get(id)?.get(&game_mode)?;
+            Some(json!({
+                "type": "playerSeason",
+                "attributes": {"gameModeStats": {game_mode.clone(): stats}},
+                "relationships": {
+                    "player": {"data": {"type": "player", "id": id}},
+                    "season": {"data": {"type": "season", "id": season_id}},
+                },
+            }))
+        })
+        .collect();
+
+    if data.is_empty() {
+        return not_found(format!("no {} stats for {:?}", game_mode, ids));
+    }
+    json_response(StatusCode::OK, &json!({"data": data, "links": {}, "meta": {}}))
+}
+
+fn ranked_stats(config: &Config, player_id: String, season_id: String) -> Response {
+    let Some(fixture) = read_fixture(config, "ranked_stats.json") else {
+        return not_found("fixtures/ranked_stats.json missing".to_string());
+    };
+    let Some(stats) = fixture.get(&player_id) else {
+        return not_found(format!("no ranked stats for {}", player_id));
+    };
+
+    json_response(StatusCode::OK, &json!({
+        "data": {
+            "type": "rankedplayerstats",
+            "attributes": {"rankedGameModeStats": stats},
+            "relationships": {
+                "player": {"data": {"type": "player", "id": player_id}},
+                "season": {"data": {"type": "season", "id": season_id}},
+            },
+        },
+        "links": {},
+        "meta": {},
+    }))
+}
+
+/// `/players/{id}/weapon_mastery` and `/players/{id}/survival_mastery`, from
+/// `fixtures/{endpoint}.json` keyed by account id.
+fn mastery(config: &Config, player_id: String, endpoint: &str) -> Response {
+    let Some(fixture) = read_fixture(config, &format!("{}.json", endpoint)) else {
+        return not_found(format!("fixtures/{}.json missing", endpoint));
+    };
+    let Some(attributes) = fixture.get(&player_id) else {
+        return not_found(format!("no {} for {}", endpoint, player_id));
+    };
+
+    let data_type = if endpoint == "weapon_mastery" { "weaponMasterySummary" } else { "survivalMasterySummary" };
+    json_response(StatusCode::OK, &json!({
+        "data": {"type": data_type, "id": player_id, "attributes": attributes},
+        "links": {},
+        "meta": {},
+    }))
+}
+
+#[tokio::main]
+async fn main() {
+    let port: u16 = std::env::var("MOCK_PORT").ok().and_then(|port| port.parse().ok()).unwrap_or(8080);
+    let config = Config {
+        fixtures: PathBuf::from(std::env::var("FIXTURES_DIR").unwrap_or_else(|_| "fixtures".to_string())),
+        public_url: std::env::var("MOCK_PUBLIC_URL").unwrap_or_else(|_| format!("http://127.0.0.1:{}", port)),
+    };
+    let limiter: RateLimiter = Arc::new(Mutex::new(RateWindow {
+        limit: std::env::var("MOCK_RATE_LIMIT").ok().and_then(|limit| limit.parse().ok()).unwrap_or(10),
+        started: now_secs(),
+        used: 0,
+    }));
+
+    let with_config = {
+        let config = config.clone();
+        warp::any().map(move || config.clone())
+    };
+    let with_limiter = warp::any().map(move || limiter.clone());
+
+    let get_players = warp::path!("shards" / String / "players")
+        .and(warp::query::<HashMap<String, String>>())
+        .and(with_config.clone())
+        .and(with_limiter.clone())
+        .map(|shard: String, query: HashMap<String, String>, config: Config, limiter: RateLimiter| {
+            rate_limited(&limiter, || players(&config, shard, query))
+        });
+
+    let get_match = warp::path!("shards" / String / "matches" / String)
+        .and(with_config.clone())
+        .map(|_shard: String, match_id: String, config: Config| match_data(&config, match_id));
+
+    let get_telemetry = warp::path!("telemetry" / String)
+        .and(with_config.clone())
+        .map(|match_id: String, config: Config| telemetry(&config, match_id));
+
+    let get_seasons = warp::path!("shards" / String / "seasons")
+        .and(with_config.clone())
+        .and(with_limiter.clone())
+        .map(|_shard: String, config: Config, limiter: RateLimiter| rate_limited(&limiter, || seasons(&config)));
+
+    let get_season_stats = warp::path!("shards" / String / "seasons" / String / "gameMode" / String / "players")
+        .and(warp::query::<HashMap<String, String>>())
+        .and(with_config.clone())
+        .and(with_limiter.clone())
+        .map(|_shard: String, season_id: String, game_mode: String, query: HashMap<String, String>, config: Config, limiter: RateLimiter| {
+            rate_limited(&limiter, || season_stats(&config, season_id, game_mode, query))
+        });
+
+    let get_ranked_stats = warp::path!("shards" / String / "players" / String / "seasons" / String / "ranked")
+        .and(with_config.clone())
+        .and(with_limiter.clone())
+        .map(|_shard: String, player_id: String, season_id: String, config: Config, limiter: RateLimiter| {
+            rate_limited(&limiter, || ranked_stats(&config, player_id, season_id))
+        });
+
+    let get_mastery = warp::path!("shards" / String / "players" / String / String)
+        .and(with_config.clone())
+        .and(with_limiter.clone())
+        .and_then(|_shard: String, player_id: String, endpoint: String, config: Config, limiter: RateLimiter| async move {
+            if endpoint != "weapon_mastery" && endpoint != "survival_mastery" {
+                return Err(warp::reject::not_found());
+            }
+            Ok(rate_limited(&limiter, || mastery(&config, player_id, &endpoint)))
+        });
+
+    let routes = warp::get().and(
+        get_players
+            .or(get_match)
+            .or(get_telemetry)
+            .or(get_seasons)
+            .or(get_season_stats)
+            .or(get_ranked_stats)
+            .or(get_mastery),
+    );
+
+    println!("Mock PUBG API serving {:?} at {}", config.fixtures, config.public_url);
+    warp::serve(routes).run(([0, 0, 0, 0], port)).await;
+}
@@ -6,11 +6,20 @@ use serde_json::Value;
 use std::collections::HashMap;
 use shared_utils::redis_loader::load_matches_summary_data_into_redis;
 use shared_utils::redis_loader::load_single_match_to_redis;
+use shared_utils::redis_loader::{load_match_paths_to_redis, match_paths_key};
+use shared_utils::redis_loader::{load_match_zones_to_redis, match_zones_key};
+use shared_utils::redis_loader::{load_landings_to_redis, LANDINGS_KEY};
+use shared_utils::redis_loader::{load_player_past_season_to_redis, load_player_season_to_redis, past_season_key, season_key, SEASON_PLAYERS_KEY};
+use shared_utils::redis_loader::{load_player_season_history_to_redis, season_history_key};
+use shared_utils::redis_loader::{load_player_ranked_to_redis, ranked_history_key, ranked_key};
+use shared_utils::redis_loader::{load_player_stats_to_redis, player_stats_key};
+use shared_utils::redis_loader::{load_player_aliases_to_redis, player_alias_key};
+use chrono::{Datelike, NaiveDate};
+use shared_utils::paths::data_path;
 
 fn get_matches() -> Vec<String> {
     let mut contents = String::new();
-    let file_path = "data/matches.json";
-    if let Ok(mut file) = File::open(file_path) {
+    if let Ok(mut file) = File::open(data_path("matches.json")) {
         if file.read_to_string(&mut contents).is_ok() {
             if let Ok(parsed_data) = serde_json::from_str::<Vec<HashMap<String, Value>>>(&contents) {
                 return parsed_data.iter()
@@ -22,18 +31,273 @@ fn get_matches() -> Vec<String> {
     Vec::new()
 }
 
+/// Player names that have a `{folder}/{name}.json` file.
+fn get_players_with_file(folder: &str) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(data_path(folder)) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".json").map(String::from))
+        .collect()
+}
+
+const DEFAULT_LEADERBOARD_MODE: &str = "squad-fpp";
+const DEFAULT_LEADERBOARD_STAT: &str = "kills";
+
+// kd, winRate ja avgDamage lasketaan, muut luetaan suoraan gameModeStats:sta
+fn leaderboard_value(stats: &Value, stat: &str) -> Option<f64> {
+    let number = |field: &str| stats.get(field).and_then(Value::as_f64);
+    let rounds = number("roundsPlayed")?;
+    match stat {
+        "kd" => Some(number("kills")? / (rounds - number("wins")?).max(1.0)),
+        "winRate" => Some(if rounds > 0.0 { number("wins")? / rounds } else { 0.0 }),
+        "avgDamage" => Some(if rounds > 0.0 { number("damageDealt")? / rounds } else { 0.0 }),
+        _ => number(stat),
+    }
+}
+
+/// Players ranked by `stat` in `mode`, players without games in the mode are left out.
+fn season_leaderboard(players: &[Value], mode: &str, stat: &str) -> Value {
+    let mut rows: Vec<(f64, Value)> = players
+        .iter()
+        .filter_map(|player| {
+            let stats = player.get("game_modes")?.get(mode)?;
+            if stats.get("roundsPlayed").and_then(Value::as_u64) == Some(0) {
+                return None;
+            }
+            let value = leaderboard_value(stats, stat)?;
+            Some((value, serde_json::json!({
+                "name": player.get("name"),
+                "value": value,
+                "roundsPlayed": stats.get("roundsPlayed"),
+            })))
+        })
+        .collect();
+    rows.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    serde_json::json!({
+        "mode": mode,
+        "stat": stat,
+        "players": rows.into_iter().map(|(_, row)| row).collect::<Vec<_>>(),
+    })
+}
+
+#[derive(Default)]
+struct DropTotals {
+    drops: u64,
+    hot_drops: u64,
+    survived: u64,
+    teams_nearby: u64,
+}
+
+impl DropTotals {
+    fn add(&mut self, squad: &Value) {
+        self.drops += 1;
+        self.hot_drops += u64::from(squad.get("hot_drop").and_then(Value::as_bool).unwrap_or(false));
+        self.survived += u64::from(squad.get("survived").and_then(Value::as_bool).unwrap_or(false));
+        self.teams_nearby += squad.get("teams_nearby").and_then(Value::as_u64).unwrap_or(0);
+    }
+
+    fn to_json(&self) -> Value {
+        let drops = self.drops.max(1) as f64;
+        serde_json::json!({
+            "drops": self.drops,
+            "hot_drops": self.hot_drops,
+            "survived": self.survived,
+            "survival_rate": self.survived as f64 / drops,
+            "avg_teams_nearby": self.teams_nearby as f64 / drops,
+        })
+    }
+}
+
+/// Drops of the tracked squads summed per map and per landing zone, optionally
+/// only in one game mode. Zones are sorted by drops, most used first.
+fn landing_summary(landings: &[Value], mode: Option<&str>) -> Value {
+    let mut maps: HashMap<String, (DropTotals, HashMap<String, DropTotals>)> = HashMap::new();
+    for landing in landings {
+        if mode.is_some_and(|mode| landing.get("game_mode").and_then(Value::as_str) != Some(mode)) {
+            continue;
+        }
+        let map_name = landing.get("map_name").and_then(Value::as_str).unwrap_or("unknown");
+        let squads = landing.get("squads").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+        for squad in squads {
+            let zone = squad.get("zone").and_then(Value::as_str).unwrap_or("unknown");
+            let (totals, zones) = maps.entry(map_name.to_string()).or_default();
+            totals.add(squad);
+            zones.entry(zone.to_string()).or_default().add(squad);
+        }
+    }
+
+    let mut rows: Vec<(String, Value)> = maps
+        .into_iter()
+        .map(|(map_name, (totals, zones))| {
+            let mut zones: Vec<(String, DropTotals)> = zones.into_iter().collect();
+            zones.sort_by(|a, b| b.1.drops.cmp(&a.1.drops).then_with(|| a.0.cmp(&b.0)));
+            let zones: Vec<Value> = zones
+                .into_iter()
+                .map(|(zone, totals)| {
+                    let mut row = totals.to_json();
+                    row["zone"] = Value::from(zone);
+                    row
+                })
+                .collect();
+            let mut row = totals.to_json();
+            row["map_name"] = Value::from(map_name.clone());
+            row["zones"] = Value::from(zones);
+            (map_name, row)
+        })
+        .collect();
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+    serde_json::json!({
+        "mode": mode.unwrap_or("all"),
+        "maps": rows.into_iter().map(|(_, row)| row).collect::<Vec<_>>(),
+    })
+}
+
+const PROGRESS_STATS: [&str; 6] = ["kills", "damageDealt", "wins", "rankPoints", "top10s", "roundsPlayed"];
+
+// rank pointit ovat pelimuotokohtaisia, niiden summa ei kerro mitään
+const MODE_ONLY_STATS: [&str; 1] = ["rankPoints"];
+
+// yhden snapshotin luvut, joko yhdestä pelimuodosta tai kaikista yhteensä
+fn snapshot_totals(snapshot: &Value, mode: Option<&str>) -> HashMap<&'static str, f64> {
+    let stats: Vec<&'static str> = PROGRESS_STATS.into_iter().filter(|stat| mode.is_some() || !MODE_ONLY_STATS.contains(stat)).collect();
+    let mut totals: HashMap<&'static str, f64> = stats.iter().map(|stat| (*stat, 0.0)).collect();
+    let Some(game_modes) = snapshot.get("game_modes").and_then(Value::as_object) else {
+        return totals;
+    };
+    for (game_mode, game_stats) in game_modes {
+        if mode.is_some_and(|mode| mode != game_mode) {
+            continue;
+        }
+        for stat in &stats {
+            *totals.get_mut(stat).unwrap() += game_stats.get(*stat).and_then(Value::as_f64).unwrap_or(0.0);
+        }
+    }
+    totals
+}
+
+fn period_of(date: &str, period: &str) -> Option<String> {
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    match period {
+        "week" => Some(format!("{}-W{:02}", day.iso_week().year(), day.iso_week().week())),
+        _ => Some(day.to_string()),
+    }
+}
+
+/// Change of the season stats per day or week, from the daily snapshots.
+/// Each period is compared to the last snapshot of the period before it, the
+/// first period to its own first snapshot. A new season starts from zero.
+/// Rank points are only given for a single `mode`.
+fn season_progress(history: &[Value], mode: Option<&str>, period: &str) -> Value {
+    let mut periods: Vec<(String, &Value, &Value)> = Vec::new();
+    for snapshot in history {
+        let Some(key) = snapshot.get("date").and_then(Value::as_str).and_then(|date| period_of(date, period)) else {
+            continue;
+        };
+        match periods.last_mut() {
+            Some((last_key, _, last)) if *last_key == key => *last = snapshot,
+            _ => periods.push((key, snapshot, snapshot)),
+        }
+    }
+
+    let mut points = Vec::new();
+    let mut previous: Option<&Value> = None;
+    for (key, first, last) in periods {
+        let baseline = match previous {
+            Some(previous) if previous.get("season_id") == last.get("season_id") => snapshot_totals(previous, mode),
+            Some(_) => snapshot_totals(&Value::Null, mode),
+            None => snapshot_totals(first, mode),
+        };
+        let totals = snapshot_totals(last, mode);
+        let deltas: HashMap<&str, f64> = totals.iter().map(|(stat, value)| (*stat, value - baseline[stat])).collect();
+        points.push(serde_json::json!({
+            "period": key,
+            "date": last.get("date"),
+            "season_id": last.get("season_id"),
+            "totals": totals,
+            "deltas": deltas,
+        }));
+        previous = Some(last);
+    }
+
+    serde_json::json!({
+        "period": period,
+        "mode": mode.unwrap_or("all"),
+        "points": points,
+    })
+}
+
+// /players/{name}/{route} -> {folder}:{name}
+const PLAYER_STATS_FOLDERS: [&str; 3] = ["lifetime_stats", "weapon_mastery", "survival_mastery"];
+
+fn player_stats_folder(route: &str) -> Option<&'static str> {
+    match route {
+        "lifetime" => Some("lifetime_stats"),
+        "weapon_mastery" => Some("weapon_mastery"),
+        "survival_mastery" => Some("survival_mastery"),
+        _ => None,
+    }
+}
+
+/// Current name of a player by any of their names, from the `player_alias:` keys.
+fn resolve_player_name(connection: &mut redis::Connection, player_name: &str) -> String {
+    connection
+        .get::<_, Option<String>>(player_alias_key(player_name))
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| player_name.to_string())
+}
+
+fn redis_connection() -> redis::RedisResult<redis::Connection> {
+    let redis_url = std::env::var("REDIS_URL").unwrap_or("redis://127.0.0.1/".to_string());
+    redis::Client::open(redis_url)?.get_connection()
+}
+
 #[tokio::main]
 async fn main() {
-    let match_ids = get_matches();
     dotenv::dotenv().ok();
+    let match_ids = get_matches();
 
     let _ = load_matches_summary_data_into_redis();
 
     for file_id in &match_ids {
-        let _ = load_single_match_to_redis(&file_id);
+        let _ = load_single_match_to_redis(file_id);
+        let _ = load_match_paths_to_redis(file_id);
+        let _ = load_match_zones_to_redis(file_id);
         println!("file name {:?}", file_id);
     }
 
+    let _ = load_landings_to_redis();
+    let _ = load_player_aliases_to_redis();
+
+    for player_name in get_players_with_file("season_stats") {
+        let _ = load_player_season_to_redis(&player_name);
+        let _ = load_player_season_history_to_redis(&player_name);
+    }
+
+    // menneet kaudet ovat omissa kansioissaan season_stats/{season}/
+    for entry in std::fs::read_dir(data_path("season_stats")).into_iter().flatten().filter_map(|entry| entry.ok()) {
+        let Some(season_id) = entry.file_name().to_str().filter(|_| entry.path().is_dir()).map(String::from) else {
+            continue;
+        };
+        for player_name in get_players_with_file(&format!("season_stats/{}", season_id)) {
+            let _ = load_player_past_season_to_redis(&season_id, &player_name);
+        }
+    }
+
+    for player_name in get_players_with_file("ranked_stats") {
+        let _ = load_player_ranked_to_redis(&player_name);
+    }
+
+    for folder in PLAYER_STATS_FOLDERS {
+        for player_name in get_players_with_file(folder) {
+            let _ = load_player_stats_to_redis(folder, &player_name);
+        }
+    }
+
     println!("Starting warp...");
     let get_match_data = warp::path!("match" / String)
         .map(|file_id: String| {
@@ -55,6 +319,66 @@ async fn main() {
             }
         });
 
+    let match_paths_data = warp::path!("match" / String / "paths")
+        .map(|match_id: String| {
+            let Ok(mut connection) = redis_connection() else {
+                return warp::reply::json(&serde_json::json!({"error": "Failed to connect to Redis"}));
+            };
+            match connection.get::<_, Option<String>>(match_paths_key(&match_id)) {
+                Ok(Some(json_data)) => match serde_json::from_str::<Value>(&json_data) {
+                    Ok(parsed_data) => warp::reply::json(&parsed_data),
+                    Err(_) => warp::reply::json(&serde_json::json!({"error": "Invalid JSON format"})),
+                },
+                Ok(None) => warp::reply::json(&serde_json::json!({"error": format!("No paths for match {}", match_id)})),
+                Err(_) => warp::reply::json(&serde_json::json!({"error": "Failed to fetch data from Redis"})),
+            }
+        });
+
+    let match_zones_data = warp::path!("match" / String / "zones")
+        .map(|match_id: String| {
+            let Ok(mut connection) = redis_connection() else {
+                return warp::reply::json(&serde_json::json!({"error": "Failed to connect to Redis"}));
+            };
+            match connection.get::<_, Option<String>>(match_zones_key(&match_id)) {
+                Ok(Some(json_data)) => match serde_json::from_str::<Value>(&json_data) {
+                    Ok(parsed_data) => warp::reply::json(&parsed_data),
+                    Err(_) => warp::reply::json(&serde_json::json!({"error": "Invalid JSON format"})),
+                },
+                Ok(None) => warp::reply::json(&serde_json::json!({"error": format!("No zones for match {}", match_id)})),
+                Err(_) => warp::reply::json(&serde_json::json!({"error": "Failed to fetch data from Redis"})),
+            }
+        });
+
+    let match_landing_data = warp::path!("match" / String / "landing")
+        .map(|match_id: String| {
+            let Ok(mut connection) = redis_connection() else {
+                return warp::reply::json(&serde_json::json!({"error": "Failed to connect to Redis"}));
+            };
+            let landings: Vec<Value> = match connection.get::<_, Option<String>>(LANDINGS_KEY) {
+                Ok(json_data) => json_data.and_then(|json_data| serde_json::from_str(&json_data).ok()).unwrap_or_default(),
+                Err(_) => return warp::reply::json(&serde_json::json!({"error": "Failed to fetch data from Redis"})),
+            };
+            match landings.into_iter().find(|landing| landing.get("match_id").and_then(Value::as_str) == Some(match_id.as_str())) {
+                Some(landing) => warp::reply::json(&landing),
+                None => warp::reply::json(&serde_json::json!({"error": format!("No landing for match {}", match_id)})),
+            }
+        });
+
+    let landings_data = warp::path!("landings")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|query: HashMap<String, String>| {
+            let Ok(mut connection) = redis_connection() else {
+                return warp::reply::json(&serde_json::json!({"error": "Failed to connect to Redis"}));
+            };
+            match connection.get::<_, Option<String>>(LANDINGS_KEY) {
+                Ok(json_data) => {
+                    let landings: Vec<Value> = json_data.and_then(|json_data| serde_json::from_str(&json_data).ok()).unwrap_or_default();
+                    warp::reply::json(&landing_summary(&landings, query.get("mode").map(String::as_str)))
+                }
+                Err(_) => warp::reply::json(&serde_json::json!({"error": "Failed to fetch data from Redis"})),
+            }
+        });
+
     let matches_summary_data = warp::path!("matches")
         .and_then(|| async move {
             let redis_url = std::env::var("REDIS_URL").unwrap_or("redis://127.0.0.1/".to_string());
@@ -66,10 +390,118 @@ async fn main() {
             Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::from_str::<serde_json::Value>(&json_data).unwrap()))
         });
 
+    // ?season=division.bro.official.pc-2018-34 hakee menneen kauden
+    let player_season_data = warp::path!("players" / String / "season")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|player_name: String, query: HashMap<String, String>| {
+            let Ok(mut connection) = redis_connection() else {
+                return warp::reply::json(&serde_json::json!({"error": "Failed to connect to Redis"}));
+            };
+            let player_name = resolve_player_name(&mut connection, &player_name);
+            let key = match query.get("season") {
+                Some(season_id) => past_season_key(season_id, &player_name),
+                None => season_key(&player_name),
+            };
+
+            match connection.get::<_, Option<String>>(key) {
+                Ok(Some(json_data)) => {
+                    match serde_json::from_str::<serde_json::Value>(&json_data) {
+                        Ok(parsed_data) => warp::reply::json(&parsed_data),
+                        Err(_) => warp::reply::json(&serde_json::json!({"error": "Invalid JSON format"})),
+                    }
+                }
+                Ok(None) => warp::reply::json(&serde_json::json!({"error": format!("No season stats for player: {}", player_name)})),
+                Err(_) => warp::reply::json(&serde_json::json!({"error": "Failed to fetch data from Redis"})),
+            }
+        });
+
+    let player_season_progress = warp::path!("players" / String / "season" / "progress")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|player_name: String, query: HashMap<String, String>| {
+            let period = match query.get("period").map(String::as_str) {
+                None | Some("day") => "day",
+                Some("week") => "week",
+                Some(other) => return warp::reply::json(&serde_json::json!({"error": format!("Unknown period: {}, expected day or week", other)})),
+            };
+            let Ok(mut connection) = redis_connection() else {
+                return warp::reply::json(&serde_json::json!({"error": "Failed to connect to Redis"}));
+            };
+            let player_name = resolve_player_name(&mut connection, &player_name);
+
+            match connection.get::<_, Option<String>>(season_history_key(&player_name)) {
+                Ok(Some(json_data)) => match serde_json::from_str::<Vec<Value>>(&json_data) {
+                    Ok(history) => warp::reply::json(&season_progress(&history, query.get("mode").map(String::as_str), period)),
+                    Err(_) => warp::reply::json(&serde_json::json!({"error": "Invalid JSON format"})),
+                },
+                Ok(None) => warp::reply::json(&serde_json::json!({"error": format!("No season history for player: {}", player_name)})),
+                Err(_) => warp::reply::json(&serde_json::json!({"error": "Failed to fetch data from Redis"})),
+            }
+        });
+
+    let player_ranked_data = warp::path!("players" / String / "ranked")
+        .map(|player_name: String| {
+            let Ok(mut connection) = redis_connection() else {
+                return warp::reply::json(&serde_json::json!({"error": "Failed to connect to Redis"}));
+            };
+            let player_name = resolve_player_name(&mut connection, &player_name);
+
+            let stats = match connection.get::<_, Option<String>>(ranked_key(&player_name)) {
+                Ok(Some(json_data)) => serde_json::from_str::<Value>(&json_data).unwrap_or(Value::Null),
+                Ok(None) => return warp::reply::json(&serde_json::json!({"error": format!("No ranked stats for player: {}", player_name)})),
+                Err(_) => return warp::reply::json(&serde_json::json!({"error": "Failed to fetch data from Redis"})),
+            };
+            let history = connection
+                .get::<_, Option<String>>(ranked_history_key(&player_name))
+                .ok()
+                .flatten()
+                .and_then(|json_data| serde_json::from_str::<Value>(&json_data).ok())
+                .unwrap_or_else(|| serde_json::json!([]));
+
+            warp::reply::json(&serde_json::json!({"stats": stats, "history": history}))
+        });
+
+    let player_stats_data = warp::path!("players" / String / String)
+        .and_then(|player_name: String, route: String| async move {
+            let Some(folder) = player_stats_folder(&route) else {
+                return Err(warp::reject::not_found());
+            };
+            let Ok(mut connection) = redis_connection() else {
+                return Ok(warp::reply::json(&serde_json::json!({"error": "Failed to connect to Redis"})));
+            };
+            let player_name = resolve_player_name(&mut connection, &player_name);
+
+            Ok(match connection.get::<_, Option<String>>(player_stats_key(folder, &player_name)) {
+                Ok(Some(json_data)) => match serde_json::from_str::<Value>(&json_data) {
+                    Ok(parsed_data) => warp::reply::json(&parsed_data),
+                    Err(_) => warp::reply::json(&serde_json::json!({"error": "Invalid JSON format"})),
+                },
+                Ok(None) => warp::reply::json(&serde_json::json!({"error": format!("No {} for player: {}", route, player_name)})),
+                Err(_) => warp::reply::json(&serde_json::json!({"error": "Failed to fetch data from Redis"})),
+            })
+        });
+
+    let season_leaderboard_data = warp::path!("season" / "leaderboard")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|query: HashMap<String, String>| {
+            let mode = query.get("mode").map(String::as_str).unwrap_or(DEFAULT_LEADERBOARD_MODE);
+            let stat = query.get("stat").map(String::as_str).unwrap_or(DEFAULT_LEADERBOARD_STAT);
+            let Ok(mut connection) = redis_connection() else {
+                return warp::reply::json(&serde_json::json!({"error": "Failed to connect to Redis"}));
+            };
+
+            let player_names: Vec<String> = connection.smembers(SEASON_PLAYERS_KEY).unwrap_or_default();
+            let players: Vec<Value> = player_names
+                .iter()
+                .filter_map(|player_name| connection.get::<_, Option<String>>(season_key(player_name)).ok().flatten())
+                .filter_map(|json_data| serde_json::from_str(&json_data).ok())
+                .collect();
+            warp::reply::json(&season_leaderboard(&players, mode, stat))
+        });
+
     let redis_url3 = std::env::var("REDIS_URL").unwrap_or("redis://127.0.0.1/".to_string());
     println!("Server running at {:?}", redis_url3);
 
-    warp::serve(get_match_data.or(matches_summary_data))
+    warp::serve(get_match_data.or(match_paths_data).or(match_zones_data).or(match_landing_data).or(matches_summary_data).or(landings_data).or(player_season_data).or(player_season_progress).or(player_ranked_data).or(player_stats_data).or(season_leaderboard_data))
         .run(([0, 0, 0, 0], 3030))
         .await;
 }
@@ -1,18 +1,60 @@
+pub mod paths {
+    use std::path::PathBuf;
+    use std::sync::OnceLock;
+
+    static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+    /// Uses `dir` instead of `DATA_DIR` for the rest of the process. Only the
+    /// first call has an effect.
+    pub fn set_data_dir(dir: impl Into<PathBuf>) {
+        let _ = DATA_DIR.set(dir.into());
+    }
+
+    /// Root of the stored match data, the one given to [`set_data_dir`],
+    /// `DATA_DIR` or `data` in the working directory.
+    pub fn data_dir() -> PathBuf {
+        if let Some(dir) = DATA_DIR.get() {
+            return dir.clone();
+        }
+        PathBuf::from(std::env::var("DATA_DIR").unwrap_or_else(|_| "data".to_string()))
+    }
+
+    /// `relative` inside [`data_dir`].
+    pub fn data_path(relative: &str) -> PathBuf {
+        data_dir().join(relative)
+    }
+}
+
 pub mod redis_loader {
+    use crate::paths::data_path;
     use redis::Commands;
     use std::fs::File;
     use std::io::Read;
+    use std::sync::OnceLock;
 
-    pub fn load_matches_summary_data_into_redis() -> redis::RedisResult<()> {
-        let file_name = "data/matches.json";
+    static REDIS_URL: OnceLock<String> = OnceLock::new();
 
-        let mut file = File::open(&file_name).expect("Failed to open JSON file");
+    /// Uses `url` instead of `REDIS_URL` for the rest of the process. Only the
+    /// first call has an effect.
+    pub fn set_redis_url(url: impl Into<String>) {
+        let _ = REDIS_URL.set(url.into());
+    }
+
+    fn redis_url() -> String {
+        match REDIS_URL.get() {
+            Some(url) => url.clone(),
+            None => std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string()),
+        }
+    }
+
+    pub fn load_matches_summary_data_into_redis() -> redis::RedisResult<()> {
+        let mut file = File::open(data_path("matches.json"))?;
 
         let mut json_data = String::new();
-        file.read_to_string(&mut json_data).expect("Failed to read data from file");
+        file.read_to_string(&mut json_data)?;
 
-        let client = redis::Client::open(std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string())).expect("Failed to connect to Redis");
-        let mut connection = client.get_connection().expect("Failed to get Redis conncetion");
+        let client = redis::Client::open(redis_url())?;
+        let mut connection = client.get_connection()?;
 
         let _ : () = connection.set("match_summaries", json_data)?;
         println!("Updated Redis with summary data.");
@@ -20,13 +62,12 @@ pub mod redis_loader {
     }
 
     pub fn load_single_match_to_redis(match_id: &str) -> redis::RedisResult<()> {
-        let file_path = format!("data/matches/{}.json", match_id);
-        let mut file = File::open(&file_path).expect("Failed to open match JSON file");
+        let mut file = File::open(data_path(&format!("matches/{}.json", match_id)))?;
 
         let mut json_data = String::new();
-        file.read_to_string(&mut json_data).expect("Failed to read data from file");
-        let client = redis::Client::open(std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string())).expect("Failed to connect to Redis");
-        let mut connection = client.get_connection().expect("Failed to get Redis conncetion");
+        file.read_to_string(&mut json_data)?;
+        let client = redis::Client::open(redis_url())?;
+        let mut connection = client.get_connection()?;
 
         let key = format!("match:{}", match_id);
         let _ : () = connection.set(key, json_data)?;
@@ -36,17 +77,218 @@ pub mod redis_loader {
     }
 
     pub fn delete_single_match_from_redis(match_id: &str) -> redis::RedisResult<()> {
-        let client = redis::Client::open(
-               std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string())
-           ).expect("Failed to connect to Redis");
+        let client = redis::Client::open(redis_url())?;
 
-        let mut connection = client.get_connection().expect("Failed to get Redis connection");
+        let mut connection = client.get_connection()?;
 
         let key = format!("match:{}", match_id);
-        let _: () = connection.del(key)?;
+        let _: () = connection.del(&[key, match_paths_key(match_id), match_zones_key(match_id)])?;
 
         println!("Deleted match id {:?} from Redis", match_id);
 
         Ok(())
     }
+
+    pub fn match_paths_key(match_id: &str) -> String {
+        format!("match_paths:{}", match_id)
+    }
+
+    pub fn load_match_paths_to_redis(match_id: &str) -> redis::RedisResult<()> {
+        let json_data = std::fs::read_to_string(data_path(&format!("paths/{}.json", match_id)))?;
+        let client = redis::Client::open(redis_url())?;
+        let mut connection = client.get_connection()?;
+
+        let _: () = connection.set(match_paths_key(match_id), json_data)?;
+        println!("Loaded paths of match {:?} into Redis", match_id);
+        Ok(())
+    }
+
+    pub fn match_zones_key(match_id: &str) -> String {
+        format!("match_zones:{}", match_id)
+    }
+
+    pub fn load_match_zones_to_redis(match_id: &str) -> redis::RedisResult<()> {
+        let json_data = std::fs::read_to_string(data_path(&format!("zones/{}.json", match_id)))?;
+        let client = redis::Client::open(redis_url())?;
+        let mut connection = client.get_connection()?;
+
+        let _: () = connection.set(match_zones_key(match_id), json_data)?;
+        println!("Loaded zones of match {:?} into Redis", match_id);
+        Ok(())
+    }
+
+    pub const LANDINGS_KEY: &str = "landings";
+
+    /// Loads `landings.json`, the drops of every stored match, into `landings`.
+    pub fn load_landings_to_redis() -> redis::RedisResult<()> {
+        let json_data = std::fs::read_to_string(data_path("landings.json"))?;
+        let client = redis::Client::open(redis_url())?;
+        let mut connection = client.get_connection()?;
+
+        let _: () = connection.set(LANDINGS_KEY, json_data)?;
+        println!("Updated Redis with landing data.");
+        Ok(())
+    }
+
+    /// `season:{name}` key of a player, names are case insensitive in PUBG.
+    pub fn season_key(player_name: &str) -> String {
+        format!("season:{}", player_name.to_lowercase())
+    }
+
+    pub const SEASON_PLAYERS_KEY: &str = "season_players";
+
+    /// Loads `season_stats/{name}.json` into `season:{name}` and lists the
+    /// player in `season_players` for the leaderboard.
+    pub fn load_player_season_to_redis(player_name: &str) -> redis::RedisResult<()> {
+        let mut file = File::open(data_path(&format!("season_stats/{}.json", player_name)))?;
+
+        let mut json_data = String::new();
+        file.read_to_string(&mut json_data)?;
+        let client = redis::Client::open(redis_url())?;
+        let mut connection = client.get_connection()?;
+
+        let _: () = redis::pipe()
+            .atomic()
+            .set(season_key(player_name), json_data)
+            .sadd(SEASON_PLAYERS_KEY, player_name)
+            .query(&mut connection)?;
+        println!("Loaded season stats of {:?} into Redis", player_name);
+
+        Ok(())
+    }
+
+    /// `season:{season}:{name}` key of a player's past season, `season_id`
+    /// is the full id such as `division.bro.official.pc-2018-34`.
+    pub fn past_season_key(season_id: &str, player_name: &str) -> String {
+        format!("season:{}:{}", season_id, player_name.to_lowercase())
+    }
+
+    /// Loads `season_stats/{season}/{name}.json` into `season:{season}:{name}`.
+    /// Past seasons are not listed in `season_players`, the leaderboard is
+    /// always the current season.
+    pub fn load_player_past_season_to_redis(season_id: &str, player_name: &str) -> redis::RedisResult<()> {
+        let mut file = File::open(data_path(&format!("season_stats/{}/{}.json", season_id, player_name)))?;
+
+        let mut json_data = String::new();
+        file.read_to_string(&mut json_data)?;
+        let client = redis::Client::open(redis_url())?;
+        let mut connection = client.get_connection()?;
+
+        let _: () = connection.set(past_season_key(season_id, player_name), json_data)?;
+        println!("Loaded season {} stats of {:?} into Redis", season_id, player_name);
+        Ok(())
+    }
+
+    /// `season_history:{name}` key of a player's daily season snapshots.
+    pub fn season_history_key(player_name: &str) -> String {
+        format!("season_history:{}", player_name.to_lowercase())
+    }
+
+    pub fn load_player_season_history_to_redis(player_name: &str) -> redis::RedisResult<()> {
+        let mut file = File::open(data_path(&format!("season_history/{}.json", player_name)))?;
+
+        let mut json_data = String::new();
+        file.read_to_string(&mut json_data)?;
+        let client = redis::Client::open(redis_url())?;
+        let mut connection = client.get_connection()?;
+
+        let _: () = connection.set(season_history_key(player_name), json_data)?;
+        Ok(())
+    }
+
+    /// `ranked:{name}` key of a player's ranked stats.
+    pub fn ranked_key(player_name: &str) -> String {
+        format!("ranked:{}", player_name.to_lowercase())
+    }
+
+    /// `ranked_history:{name}` key of a player's RP changes.
+    pub fn ranked_history_key(player_name: &str) -> String {
+        format!("ranked_history:{}", player_name.to_lowercase())
+    }
+
+    /// Loads `ranked_stats/{name}.json` and, once the player has one,
+    /// `ranked_history/{name}.json` into Redis.
+    pub fn load_player_ranked_to_redis(player_name: &str) -> redis::RedisResult<()> {
+        let mut file = File::open(data_path(&format!("ranked_stats/{}.json", player_name)))?;
+
+        let mut json_data = String::new();
+        file.read_to_string(&mut json_data)?;
+        let client = redis::Client::open(redis_url())?;
+        let mut connection = client.get_connection()?;
+
+        let _: () = connection.set(ranked_key(player_name), json_data)?;
+        if let Ok(mut file) = File::open(data_path(&format!("ranked_history/{}.json", player_name))) {
+            let mut history_data = String::new();
+            file.read_to_string(&mut history_data)?;
+            let _: () = connection.set(ranked_history_key(player_name), history_data)?;
+        }
+        Ok(())
+    }
+
+    /// Loads `{folder}/{name}.json` into `{folder}:{name}`, for per-player
+    /// stats that are only ever read whole.
+    pub fn load_player_stats_to_redis(folder: &str, player_name: &str) -> redis::RedisResult<()> {
+        let mut file = File::open(data_path(&format!("{}/{}.json", folder, player_name)))?;
+
+        let mut json_data = String::new();
+        file.read_to_string(&mut json_data)?;
+        let client = redis::Client::open(redis_url())?;
+        let mut connection = client.get_connection()?;
+
+        let _: () = connection.set(player_stats_key(folder, player_name), json_data)?;
+        Ok(())
+    }
+
+    pub fn player_stats_key(folder: &str, player_name: &str) -> String {
+        format!("{}:{}", folder, player_name.to_lowercase())
+    }
+
+    /// `player_alias:{name}` key, the current name of a player by any of
+    /// their current or former names.
+    pub fn player_alias_key(player_name: &str) -> String {
+        format!("player_alias:{}", player_name.to_lowercase())
+    }
+
+    /// Points every name in `identities.json` to the account's current name.
+    pub fn load_player_aliases_to_redis() -> redis::RedisResult<()> {
+        let mut file = File::open(data_path("identities.json"))?;
+
+        let mut json_data = String::new();
+        file.read_to_string(&mut json_data)?;
+        let identities: serde_json::Value = serde_json::from_str(&json_data)
+            .map_err(|e| redis::RedisError::from((redis::ErrorKind::TypeError, "invalid identities.json", e.to_string())))?;
+
+        let client = redis::Client::open(redis_url())?;
+        let mut connection = client.get_connection()?;
+
+        let mut pipe = redis::pipe();
+        for identity in identities.as_object().into_iter().flat_map(|players| players.values()) {
+            let Some(current_name) = identity.get("name").and_then(|name| name.as_str()) else { continue };
+            for record in identity.get("names").and_then(|names| names.as_array()).into_iter().flatten() {
+                if let Some(name) = record.get("name").and_then(|name| name.as_str()) {
+                    pipe.set(player_alias_key(name), current_name);
+                }
+            }
+        }
+        let _: () = pipe.query(&mut connection)?;
+        Ok(())
+    }
+
+    /// Drops every per-player key of `player_name`, after a rename.
+    pub fn forget_player_in_redis(player_name: &str) -> redis::RedisResult<()> {
+        let client = redis::Client::open(redis_url())?;
+        let mut connection = client.get_connection()?;
+
+        let mut pipe = redis::pipe();
+        pipe.srem(SEASON_PLAYERS_KEY, player_name)
+            .del(season_key(player_name))
+            .del(season_history_key(player_name))
+            .del(ranked_key(player_name))
+            .del(ranked_history_key(player_name));
+        for folder in ["lifetime_stats", "weapon_mastery", "survival_mastery"] {
+            pipe.del(player_stats_key(folder, player_name));
+        }
+        let _: () = pipe.query(&mut connection)?;
+        Ok(())
+    }
 }